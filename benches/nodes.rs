@@ -0,0 +1,100 @@
+//! Per-node overhead of the closure backend.
+//!
+//! Each benchmark runs a loop of [`ITERATIONS`] iterations whose body
+//! evaluates a single node next to the counter's decrement; the per-node
+//! overhead is `(bench - back_edge) / ITERATIONS`, where `back_edge`
+//! measures the loop itself.
+
+#![feature(test)]
+
+extern crate test;
+
+use rast_jit_vm_simple::*;
+use test::Bencher;
+
+const ITERATIONS: i32 = 1000;
+
+/// let x = input;
+/// let n = ITERATIONS;
+///
+/// while n > 0 {
+///     n = n - 1;
+///     <node>
+/// }
+///
+/// x
+fn bench_node(b: &mut Bencher, node: Option<Node>) {
+    let mut body = vec![Node::Assign {
+        name: "n",
+        value: Box::new(Node::Sub {
+            lhs: Box::new(Node::Var("n")),
+            rhs: Box::new(Node::Const(Value::Int(1))),
+        }),
+    }];
+
+    body.extend(node);
+
+    let prog = TypedProgram::<i32, i32>::new(Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(vec![
+            Node::Let {
+                name: "x",
+                value: Box::new(Node::Var("input")),
+            },
+            Node::Let {
+                name: "n",
+                value: Box::new(Node::Const(Value::Int(ITERATIONS))),
+            },
+            Node::While {
+                cond: Box::new(Node::Gt {
+                    lhs: Box::new(Node::Var("n")),
+                    rhs: Box::new(Node::Const(Value::Int(0))),
+                }),
+                body: Box::new(Node::Block(body)),
+            },
+            Node::Var("x"),
+        ]),
+    })
+    .and_then(compile)
+    .unwrap();
+
+    b.iter(|| prog(test::black_box(1)));
+}
+
+#[bench]
+fn back_edge(b: &mut Bencher) {
+    bench_node(b, None);
+}
+
+#[bench]
+fn konst(b: &mut Bencher) {
+    bench_node(b, Some(Node::Const(Value::Int(1))));
+}
+
+#[bench]
+fn var(b: &mut Bencher) {
+    bench_node(b, Some(Node::Var("x")));
+}
+
+#[bench]
+fn add(b: &mut Bencher) {
+    bench_node(
+        b,
+        Some(Node::Add {
+            lhs: Box::new(Node::Var("x")),
+            rhs: Box::new(Node::Var("x")),
+        }),
+    );
+}
+
+#[bench]
+fn assign(b: &mut Bencher) {
+    bench_node(
+        b,
+        Some(Node::Assign {
+            name: "x",
+            value: Box::new(Node::Var("x")),
+        }),
+    );
+}
@@ -0,0 +1,72 @@
+#![feature(test)]
+
+extern crate test;
+
+use rast_jit_vm_simple::*;
+use test::Bencher;
+
+/// input + 1
+#[bench]
+fn inc(b: &mut Bencher) {
+    let inc = TypedProgram::<i32, i32>::new(Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Add {
+            lhs: Box::new(Node::Var("input")),
+            rhs: Box::new(Node::Const(Value::Int(1))),
+        },
+    })
+    .and_then(compile)
+    .unwrap();
+
+    b.iter(|| inc(test::black_box(1)));
+}
+
+/// let a = input; let b = a; ...; p
+#[bench]
+fn lets(b: &mut Bencher) {
+    let names = [
+        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p",
+    ];
+    let mut prev = "input";
+    let mut body = Vec::new();
+
+    for name in names {
+        body.push(Node::Let {
+            name,
+            value: Box::new(Node::Var(prev)),
+        });
+
+        prev = name;
+    }
+
+    body.push(Node::Var(prev));
+
+    let lets = TypedProgram::<i32, i32>::new(Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(body),
+    })
+    .and_then(compile)
+    .unwrap();
+
+    b.iter(|| lets(test::black_box(1)));
+}
+
+#[bench]
+fn corpus(b: &mut Bencher) {
+    let progs: Vec<_> = examples_corpus::all()
+        .into_iter()
+        .map(|(_, prog)| {
+            TypedProgram::<i32, i32>::new(prog)
+                .and_then(compile)
+                .unwrap()
+        })
+        .collect();
+
+    b.iter(|| {
+        for prog in &progs {
+            prog(test::black_box(10));
+        }
+    });
+}
@@ -0,0 +1,276 @@
+use crate::termination::{assigns, find_counter, Step};
+use crate::{Node, Program, Type, Value};
+use std::collections::HashMap;
+
+/// Upper bound on the number of nodes a program executes, as a function of
+/// its input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CostEstimate {
+    pub base: u64,
+
+    /// Additional nodes executed per unit of `max(input, 0)`
+    pub per_input: u64,
+}
+
+impl CostEstimate {
+    pub fn at(&self, input: i32) -> u64 {
+        let input = input.max(0) as u64;
+
+        self.base
+            .saturating_add(self.per_input.saturating_mul(input))
+    }
+
+    fn constant(base: u64) -> Self {
+        Self { base, per_input: 0 }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            base: self.base.saturating_add(other.base),
+            per_input: self.per_input.saturating_add(other.per_input),
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        Self {
+            base: self.base.max(other.base),
+            per_input: self.per_input.max(other.per_input),
+        }
+    }
+
+    /// Returns `None` if the result wouldn't be linear in the input.
+    fn mul(self, other: Self) -> Option<Self> {
+        if self.per_input > 0 && other.per_input > 0 {
+            return None;
+        }
+
+        Some(Self {
+            base: self.base.saturating_mul(other.base),
+            per_input: self
+                .base
+                .saturating_mul(other.per_input)
+                .saturating_add(self.per_input.saturating_mul(other.base)),
+        })
+    }
+}
+
+impl Program {
+    /// Estimates the number of nodes executed by the program.
+    ///
+    /// Returns `None` when a loop's iteration count can't be derived (see
+    /// [`Program::termination_report()`] for the recognized loop shapes) or
+    /// when the cost grows faster than linearly with the input.
+    pub fn cost_estimate(&self) -> Option<CostEstimate> {
+        let mut env = Env::default();
+
+        if self.input == Type::Int {
+            env.insert("input", Affine { base: 0, input: 1 });
+        }
+
+        estimate(&mut env, &self.body)
+    }
+}
+
+/// Known values of variables at the current point of the program
+type Env = HashMap<&'static str, Affine>;
+
+/// `base + input * input_var`
+#[derive(Copy, Clone, Debug)]
+struct Affine {
+    base: i64,
+    input: i64,
+}
+
+fn estimate(env: &mut Env, node: &Node) -> Option<CostEstimate> {
+    match node {
+        Node::Let { name, value } | Node::Assign { name, value } => {
+            let cost = estimate(env, value)?;
+
+            match eval(env, value) {
+                Some(value) => env.insert(name, value),
+                None => env.remove(name),
+            };
+
+            Some(CostEstimate::constant(1).add(cost))
+        }
+
+        Node::Const(_) | Node::Var(_) => Some(CostEstimate::constant(1)),
+
+        // The default is evaluated only if there's no stored value
+        Node::LoadState { default, .. } => {
+            let cost = estimate(&mut env.clone(), default)?;
+
+            env.retain(|name, _| assigns(default, name) == 0);
+
+            Some(CostEstimate::constant(1).add(cost))
+        }
+
+        Node::SaveState { value, .. } => {
+            let cost = estimate(env, value)?;
+
+            Some(CostEstimate::constant(1).add(cost))
+        }
+
+        Node::Gt { lhs, rhs }
+        | Node::Lt { lhs, rhs }
+        | Node::Ge { lhs, rhs }
+        | Node::Le { lhs, rhs }
+        | Node::Eq { lhs, rhs }
+        | Node::Ne { lhs, rhs }
+        | Node::Add { lhs, rhs }
+        | Node::Sub { lhs, rhs } => {
+            let lhs = estimate(env, lhs)?;
+            let rhs = estimate(env, rhs)?;
+
+            Some(CostEstimate::constant(1).add(lhs).add(rhs))
+        }
+
+        Node::While { cond, body } => {
+            let iterations = iterations(env, cond, body)?;
+
+            env.retain(|name, _| assigns(cond, name) == 0 && assigns(body, name) == 0);
+
+            let cond = estimate(&mut env.clone(), cond)?;
+            let body = estimate(&mut env.clone(), body)?;
+
+            let cond = iterations.add(CostEstimate::constant(1)).mul(cond)?;
+
+            let body = iterations.mul(body)?;
+
+            Some(CostEstimate::constant(1).add(cond).add(body))
+        }
+
+        Node::If { cond, then, else_ } => {
+            let cond = estimate(env, cond)?;
+            let then = estimate(&mut env.clone(), then)?;
+
+            let else_ = match else_ {
+                Some(else_) => estimate(&mut env.clone(), else_)?,
+                None => CostEstimate::constant(0),
+            };
+
+            env.retain(|name, _| assigns(node, name) == 0);
+
+            Some(CostEstimate::constant(1).add(cond).add(then.max(else_)))
+        }
+
+        // Declaring a function doesn't run its body
+        Node::Fn { .. } => Some(CostEstimate::constant(1)),
+
+        // Calls might recurse arbitrarily deep
+        Node::Call { .. } => None,
+
+        // The hole's subtree could be anything
+        Node::Hole(..) => None,
+
+        Node::Block(nodes) => {
+            let outer = env.clone();
+            let mut cost = CostEstimate::constant(1);
+
+            for node in nodes {
+                cost = cost.add(estimate(env, node)?);
+            }
+
+            // Variables declared within the block go out of scope, uncovering
+            // the ones they've shadowed - unless the block might've assigned
+            // the shadowed variable before, in which case its value is unknown
+            for child in nodes {
+                if let Node::Let { name, .. } = child {
+                    match outer.get(name) {
+                        Some(value) if assigns(node, name) == 0 => env.insert(name, *value),
+                        _ => env.remove(name),
+                    };
+                }
+            }
+
+            Some(cost)
+        }
+    }
+}
+
+/// Estimates how many times the loop's body will run.
+fn iterations(env: &Env, cond: &Node, body: &Node) -> Option<CostEstimate> {
+    let counter = find_counter(cond, body)?;
+    let start = *env.get(counter.name)?;
+    let bound = eval(env, counter.bound)?;
+
+    let (mut distance, k) = match counter.step {
+        Step::Decreasing(k) => (start.sub(bound)?, k),
+        Step::Increasing(k) => (bound.sub(start)?, k),
+    };
+
+    if counter.inclusive {
+        distance.base = distance.base.checked_add(1)?;
+    }
+
+    // The input might be arbitrarily negative, so the distance can only be
+    // bounded if it doesn't decrease with the input
+    if distance.input < 0 {
+        return None;
+    }
+
+    let k = k as i64;
+
+    Some(CostEstimate {
+        base: (distance.base.max(0).checked_add(k - 1)? / k) as u64,
+        per_input: distance.input as u64,
+    })
+}
+
+fn eval(env: &Env, node: &Node) -> Option<Affine> {
+    match node {
+        Node::Const(Value::Int(value)) => Some(Affine {
+            base: *value as i64,
+            input: 0,
+        }),
+
+        Node::Var(name) => env.get(name).copied(),
+
+        Node::Add { lhs, rhs } => eval(env, lhs)?.add(eval(env, rhs)?),
+        Node::Sub { lhs, rhs } => eval(env, lhs)?.sub(eval(env, rhs)?),
+
+        _ => None,
+    }
+}
+
+/// Both operations return `None` on overflow, i.e. when the value can't be
+/// tracked anymore.
+impl Affine {
+    fn add(self, other: Self) -> Option<Self> {
+        Some(Self {
+            base: self.base.checked_add(other.base)?,
+            input: self.input.checked_add(other.input)?,
+        })
+    }
+
+    fn sub(self, other: Self) -> Option<Self> {
+        Some(Self {
+            base: self.base.checked_sub(other.base)?,
+            input: self.input.checked_sub(other.input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn overflow() {
+        let src = format!(
+            "let n = 2147483647; {} while n > 0 {{ n = n - 1 }}; n",
+            "n = n + n;".repeat(40),
+        );
+
+        let prog = parse(Box::leak(src.into_boxed_str())).unwrap();
+
+        assert_eq!(None, prog.cost_estimate());
+    }
+
+    #[test]
+    fn bound_with_side_effects() {
+        let prog = parse("let i = 0; let m = 0; while i < { m = m + 2; m } { i = i + 1 }; i");
+
+        assert_eq!(None, prog.unwrap().cost_estimate());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{Node, NodePath};
+
+/// Difference between two trees, as reported by [`diff()`]
+///
+/// Paths point into the old tree, except for [`Edit::Inserted`], which points
+/// into the new one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Changed {
+        path: NodePath,
+        from: Node,
+        to: Node,
+    },
+
+    Inserted {
+        path: NodePath,
+        node: Node,
+    },
+
+    Removed {
+        path: NodePath,
+        node: Node,
+    },
+}
+
+/// Computes a structural diff between two trees.
+///
+/// Nodes of different kinds (or with different names, values etc.) are
+/// reported as changed as a whole; otherwise their children are compared
+/// recursively, with statements of blocks aligned so that an inserted or
+/// removed statement doesn't show up as a change to all the following ones.
+pub fn diff(a: &Node, b: &Node) -> Vec<Edit> {
+    let mut edits = Vec::new();
+
+    let mut path = Paths {
+        old: NodePath::root(),
+        new: NodePath::root(),
+    };
+
+    diff_node(&mut edits, &mut path, a, b);
+    edits
+}
+
+/// Paths of the nodes being compared, which differ once statements get
+/// inserted or removed from a block
+struct Paths {
+    /// Path of the node in the old tree
+    old: NodePath,
+
+    /// Path of the node in the new tree
+    new: NodePath,
+}
+
+impl Paths {
+    fn push(&mut self, old_idx: usize, new_idx: usize) {
+        self.old.push(old_idx);
+        self.new.push(new_idx);
+    }
+
+    fn pop(&mut self) {
+        self.old.pop();
+        self.new.pop();
+    }
+}
+
+fn diff_node(edits: &mut Vec<Edit>, path: &mut Paths, a: &Node, b: &Node) {
+    if a == b {
+        return;
+    }
+
+    match (a, b) {
+        (Node::Block(a), Node::Block(b)) => {
+            diff_block(edits, path, a, b);
+        }
+
+        (Node::Let { name: a_name, .. }, Node::Let { name: b_name, .. })
+        | (Node::Assign { name: a_name, .. }, Node::Assign { name: b_name, .. })
+        | (Node::LoadState { key: a_name, .. }, Node::LoadState { key: b_name, .. })
+        | (Node::SaveState { key: a_name, .. }, Node::SaveState { key: b_name, .. })
+            if a_name == b_name =>
+        {
+            diff_children(edits, path, a, b);
+        }
+
+        (Node::Gt { .. }, Node::Gt { .. })
+        | (Node::Lt { .. }, Node::Lt { .. })
+        | (Node::Ge { .. }, Node::Ge { .. })
+        | (Node::Le { .. }, Node::Le { .. })
+        | (Node::Eq { .. }, Node::Eq { .. })
+        | (Node::Ne { .. }, Node::Ne { .. })
+        | (Node::Add { .. }, Node::Add { .. })
+        | (Node::Sub { .. }, Node::Sub { .. })
+        | (Node::While { .. }, Node::While { .. }) => {
+            diff_children(edits, path, a, b);
+        }
+
+        (
+            Node::Fn {
+                name: a_name,
+                params: a_params,
+                output: a_output,
+                ..
+            },
+            Node::Fn {
+                name: b_name,
+                params: b_params,
+                output: b_output,
+                ..
+            },
+        ) if (a_name, a_params, a_output) == (b_name, b_params, b_output) => {
+            diff_children(edits, path, a, b);
+        }
+
+        (
+            Node::Call {
+                name: a_name,
+                args: a_args,
+            },
+            Node::Call {
+                name: b_name,
+                args: b_args,
+            },
+        ) if a_name == b_name && a_args.len() == b_args.len() => {
+            diff_children(edits, path, a, b);
+        }
+
+        (Node::If { else_: a_else, .. }, Node::If { else_: b_else, .. })
+            if a_else.is_some() == b_else.is_some() =>
+        {
+            diff_children(edits, path, a, b);
+        }
+
+        _ => {
+            edits.push(Edit::Changed {
+                path: path.old.clone(),
+                from: a.clone(),
+                to: b.clone(),
+            });
+        }
+    }
+}
+
+fn diff_children(edits: &mut Vec<Edit>, path: &mut Paths, a: &Node, b: &Node) {
+    for (idx, (a, b)) in a.children().into_iter().zip(b.children()).enumerate() {
+        path.push(idx, idx);
+        diff_node(edits, path, a, b);
+        path.pop();
+    }
+}
+
+fn diff_block(edits: &mut Vec<Edit>, path: &mut Paths, a: &[Node], b: &[Node]) {
+    // lcs[i][j] = length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut removed = Vec::new();
+    let mut inserted = Vec::new();
+
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            flush_gap(edits, path, a, b, &mut removed, &mut inserted);
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            inserted.push(j);
+            j += 1;
+        } else {
+            removed.push(i);
+            i += 1;
+        }
+    }
+
+    flush_gap(edits, path, a, b, &mut removed, &mut inserted);
+}
+
+/// Reports statements between two aligned ones: pairs of removed & inserted
+/// statements are diffed against each other, the rest is reported as is.
+fn flush_gap(
+    edits: &mut Vec<Edit>,
+    path: &mut Paths,
+    a: &[Node],
+    b: &[Node],
+    removed: &mut Vec<usize>,
+    inserted: &mut Vec<usize>,
+) {
+    let paired = removed.len().min(inserted.len());
+
+    for (&i, &j) in removed.iter().zip(inserted.iter()) {
+        path.push(i, j);
+        diff_node(edits, path, &a[i], &b[j]);
+        path.pop();
+    }
+
+    for &i in &removed[paired..] {
+        path.old.push(i);
+
+        edits.push(Edit::Removed {
+            path: path.old.clone(),
+            node: a[i].clone(),
+        });
+
+        path.old.pop();
+    }
+
+    for &j in &inserted[paired..] {
+        path.new.push(j);
+
+        edits.push(Edit::Inserted {
+            path: path.new.clone(),
+            node: b[j].clone(),
+        });
+
+        path.new.pop();
+    }
+
+    removed.clear();
+    inserted.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Value};
+
+    #[test]
+    fn inserted_paths_point_into_new_tree() {
+        let a = parse("1; { 5 }").unwrap().body;
+        let b = parse("9; 1; { 5; 6 }").unwrap().body;
+
+        assert_eq!(
+            vec![
+                Edit::Inserted {
+                    path: vec![0].into(),
+                    node: Node::Const(Value::Int(9)),
+                },
+                Edit::Inserted {
+                    path: vec![2, 1].into(),
+                    node: Node::Const(Value::Int(6)),
+                },
+            ],
+            diff(&a, &b),
+        );
+    }
+
+    #[test]
+    fn removed_paths_point_into_old_tree() {
+        let a = parse("9; 1; { 5; 6 }").unwrap().body;
+        let b = parse("1; { 5 }").unwrap().body;
+
+        assert_eq!(
+            vec![
+                Edit::Removed {
+                    path: vec![0].into(),
+                    node: Node::Const(Value::Int(9)),
+                },
+                Edit::Removed {
+                    path: vec![2, 1].into(),
+                    node: Node::Const(Value::Int(6)),
+                },
+            ],
+            diff(&a, &b),
+        );
+    }
+}
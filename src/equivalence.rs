@@ -0,0 +1,274 @@
+use crate::{
+    compile_program, CompileError, CompileOptions, Compiled, Program, RuntimeContext, Thunk, Type,
+    Value,
+};
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+
+#[derive(Clone, Debug)]
+pub enum EquivalenceStrategy {
+    /// Runs both programs on every input from given range (for programs
+    /// taking an int; for ones taking a float, on every whole number from it)
+    /// or on every possible input (for other types)
+    Exhaustive { range: RangeInclusive<i32> },
+
+    /// Runs both programs on `budget` pseudo-random inputs drawn from given
+    /// range (for programs taking an int or a float) or on every possible
+    /// input (for other types)
+    Random {
+        range: RangeInclusive<i32>,
+        budget: usize,
+        seed: u64,
+    },
+}
+
+/// Input for which two programs disagree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    pub input: Value,
+
+    /// Output of the first program, or `None` if it panicked
+    pub lhs: Option<Value>,
+
+    /// Output of the second program, or `None` if it panicked
+    pub rhs: Option<Value>,
+}
+
+/// Checks whether two programs produce the same outputs, returning the first
+/// input for which they don't.
+///
+/// Panicking is treated as an output of its own, i.e. two programs that both
+/// panic for some input agree on it.
+///
+/// Fails if either of the programs doesn't compile or if they have different
+/// input or output types.
+pub fn are_equivalent(
+    lhs: &Program,
+    rhs: &Program,
+    strategy: &EquivalenceStrategy,
+) -> Result<Option<Counterexample>, CompileError> {
+    if lhs.input != rhs.input {
+        return Err(CompileError::TypeMismatch {
+            expected: lhs.input,
+            found: rhs.input,
+        });
+    }
+
+    let input_ty = lhs.input;
+    let opts = CompileOptions::default();
+    let lhs = compile_program(lhs.clone(), None, &opts)?;
+    let rhs = compile_program(rhs.clone(), None, &opts)?;
+
+    if lhs.output != rhs.output {
+        return Err(CompileError::TypeMismatch {
+            expected: lhs.output,
+            found: rhs.output,
+        });
+    }
+
+    Ok(compare(input_ty, &lhs, &rhs, strategy))
+}
+
+/// Runs two compiled programs (taking and returning the same types) against
+/// inputs chosen by `strategy`, returning the first one they disagree on.
+pub(crate) fn compare(
+    input_ty: Type,
+    lhs: &Compiled,
+    rhs: &Compiled,
+    strategy: &EquivalenceStrategy,
+) -> Option<Counterexample> {
+    inputs(input_ty, strategy).find_map(|input| {
+        let lhs = run(&lhs.thunk, lhs.stack_len, &input);
+        let rhs = run(&rhs.thunk, rhs.stack_len, &input);
+
+        (lhs != rhs).then_some(Counterexample { input, lhs, rhs })
+    })
+}
+
+fn run(thunk: &Thunk, stack_len: usize, input: &Value) -> Option<Value> {
+    let mut ctxt = RuntimeContext {
+        stack: vec![Value::Unit; stack_len],
+        depth: 0,
+    };
+
+    ctxt.stack[0] = input.clone();
+
+    panic::catch_unwind(AssertUnwindSafe(|| thunk(&mut ctxt))).ok()
+}
+
+fn inputs(ty: Type, strategy: &EquivalenceStrategy) -> Box<dyn Iterator<Item = Value>> {
+    match (ty, strategy) {
+        (Type::Unit, _) => Box::new(vec![Value::Unit].into_iter()),
+        (Type::Bool, _) => Box::new(vec![Value::Bool(false), Value::Bool(true)].into_iter()),
+
+        (Type::Int, EquivalenceStrategy::Exhaustive { range }) => {
+            Box::new(range.clone().map(Value::Int))
+        }
+
+        (Type::Float, EquivalenceStrategy::Exhaustive { range }) => {
+            Box::new(range.clone().map(|value| Value::Float(value as f64)))
+        }
+
+        (
+            Type::Int,
+            EquivalenceStrategy::Random {
+                range,
+                budget,
+                seed,
+            },
+        ) => {
+            if range.is_empty() {
+                return Box::new(None.into_iter());
+            }
+
+            let start = *range.start() as i64;
+            let span = (*range.end() as i64 - start + 1) as u64;
+            let mut rng = Rng::new(*seed);
+
+            Box::new((0..*budget).map(move |_| {
+                let offset = (rng.next() % span) as i64;
+
+                Value::Int((start + offset) as i32)
+            }))
+        }
+
+        (
+            Type::Float,
+            EquivalenceStrategy::Random {
+                range,
+                budget,
+                seed,
+            },
+        ) => {
+            if range.is_empty() {
+                return Box::new(None.into_iter());
+            }
+
+            let start = *range.start() as f64;
+            let span = *range.end() as f64 - start;
+            let mut rng = Rng::new(*seed);
+
+            Box::new((0..*budget).map(move |_| {
+                let fraction = rng.next() as f64 / u64::MAX as f64;
+
+                Value::Float(start + fraction * span)
+            }))
+        }
+    }
+}
+
+/// Xorshift-based pseudo-random number generator
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        Self(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn check(lhs: &'static str, rhs: &'static str) -> Result<Option<Counterexample>, CompileError> {
+        let strategy = EquivalenceStrategy::Exhaustive { range: 0..=10 };
+
+        are_equivalent(&parse(lhs).unwrap(), &parse(rhs).unwrap(), &strategy)
+    }
+
+    #[test]
+    fn equivalent() {
+        assert_eq!(Ok(None), check("input + input", "input - 0 + input"));
+    }
+
+    #[test]
+    fn counterexample() {
+        assert_eq!(
+            Ok(Some(Counterexample {
+                input: Value::Int(6),
+                lhs: Some(Value::Int(6)),
+                rhs: Some(Value::Int(0)),
+            })),
+            check("input", "if input > 5 { 0 } else { input }"),
+        );
+    }
+
+    #[test]
+    fn panics() {
+        let panicking = "fn f(n: int) -> int { f(n) }; f(input)";
+
+        assert_eq!(Ok(None), check(panicking, panicking));
+
+        assert_eq!(
+            Ok(Some(Counterexample {
+                input: Value::Int(0),
+                lhs: Some(Value::Int(0)),
+                rhs: None,
+            })),
+            check("input", panicking),
+        );
+    }
+
+    #[test]
+    fn random() {
+        let lhs = parse("input").unwrap();
+        let rhs = parse("if input > 5 { 0 } else { input }").unwrap();
+
+        let strategy = |range| EquivalenceStrategy::Random {
+            range,
+            budget: 100,
+            seed: 1,
+        };
+
+        assert_eq!(Ok(None), are_equivalent(&lhs, &rhs, &strategy(-100..=5)));
+
+        assert!(matches!(
+            are_equivalent(&lhs, &rhs, &strategy(-100..=100)),
+            Ok(Some(Counterexample { input: Value::Int(input), .. })) if input > 5,
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let mut float = parse("1").unwrap();
+
+        float.input = Type::Float;
+
+        assert_eq!(
+            Err(CompileError::TypeMismatch {
+                expected: Type::Int,
+                found: Type::Float,
+            }),
+            are_equivalent(
+                &parse("1").unwrap(),
+                &float,
+                &EquivalenceStrategy::Exhaustive { range: 0..=10 },
+            ),
+        );
+
+        assert_eq!(
+            Err(CompileError::TypeMismatch {
+                expected: Type::Int,
+                found: Type::Bool,
+            }),
+            check("input", "input > 0"),
+        );
+
+        assert_eq!(
+            Err(CompileError::UndefinedVar {
+                name: "x",
+                suggestion: None,
+            }),
+            check("input", "x"),
+        );
+    }
+}
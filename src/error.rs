@@ -0,0 +1,224 @@
+use crate::{NodeKind, NodePath, Type};
+use std::error::Error;
+use std::fmt;
+
+/// Reason a program couldn't be compiled
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    /// Program is nested deeper than [`MAX_DEPTH`](crate::MAX_DEPTH) or
+    /// [`CompileOptions::max_expression_depth`](crate::CompileOptions::max_expression_depth)
+    /// allows
+    TooDeep { depth: usize, max: usize },
+
+    /// Program has more nodes than
+    /// [`CompileOptions::max_nodes`](crate::CompileOptions::max_nodes) allows
+    TooManyNodes { nodes: usize, max: usize },
+
+    /// Program declares more variables than
+    /// [`CompileOptions::max_vars`](crate::CompileOptions::max_vars) allows
+    TooManyVars { vars: usize, max: usize },
+
+    /// Program nests loops deeper than
+    /// [`CompileOptions::max_loop_nesting`](crate::CompileOptions::max_loop_nesting)
+    /// allows
+    LoopsNestedTooDeeply { nesting: usize, max: usize },
+
+    /// Read or assignment of a variable that hasn't been declared; when a
+    /// declared variable is spelled similarly, it's given as `suggestion`
+    UndefinedVar {
+        name: &'static str,
+        suggestion: Option<&'static str>,
+    },
+
+    /// Function parameter declared more than once
+    DuplicateVar { name: &'static str },
+
+    /// Call of a function that hasn't been declared; when a declared function
+    /// is spelled similarly, it's given as `suggestion`
+    UndefinedFn {
+        name: &'static str,
+        suggestion: Option<&'static str>,
+    },
+
+    /// Declaration of a function whose name is already taken
+    DuplicateFn { name: &'static str },
+
+    /// Call with a different number of arguments than the function takes
+    ArgCountMismatch {
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+
+    /// Value of type `found` used where `expected` is required
+    TypeMismatch { expected: Type, found: Type },
+
+    /// Program evaluates to a type other than its declared output type (or,
+    /// when compiling a [`TypedProgram`](crate::TypedProgram), than its Rust
+    /// output type); `path` points at the node the program evaluates to
+    SignatureMismatch {
+        declared: Type,
+        inferred: Type,
+        path: NodePath,
+    },
+
+    /// Program's declared input or output type differs from the corresponding
+    /// Rust type of [`TypedProgram`](crate::TypedProgram)
+    RustTypeMismatch { declared: Type, rust: Type },
+
+    /// Operator applied to operands of types it doesn't support
+    UnsupportedOp { op: NodeKind, lhs: Type, rhs: Type },
+
+    /// Block without any statements, which has no value to evaluate to
+    EmptyBlock,
+
+    /// Program accesses state, but no
+    /// [`CompileOptions::state_store`](crate::CompileOptions::state_store)
+    /// has been provided
+    MissingStateStore,
+
+    /// Hole that hasn't been filled by
+    /// [`Program::instantiate()`](crate::Program::instantiate)
+    UnfilledHole { id: usize },
+
+    /// Hole has been filled by
+    /// [`Program::instantiate()`](crate::Program::instantiate) with a subtree
+    /// of another type
+    FillTypeMismatch {
+        id: usize,
+        expected: Type,
+        found: Type,
+    },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooDeep { depth, max } => {
+                write!(f, "program too deep: {} levels (max {})", depth, max)
+            }
+
+            Self::TooManyNodes { nodes, max } => {
+                write!(f, "program too large: {} nodes (max {})", nodes, max)
+            }
+
+            Self::TooManyVars { vars, max } => {
+                write!(f, "too many variables: {} (max {})", vars, max)
+            }
+
+            Self::LoopsNestedTooDeeply { nesting, max } => {
+                write!(
+                    f,
+                    "loops nested too deeply: {} levels (max {})",
+                    nesting, max
+                )
+            }
+
+            Self::UndefinedVar {
+                name,
+                suggestion: Some(suggestion),
+            } => {
+                write!(
+                    f,
+                    "var not defined: {} (did you mean `{}`?)",
+                    name, suggestion
+                )
+            }
+
+            Self::UndefinedVar {
+                name,
+                suggestion: None,
+            } => {
+                write!(f, "var not defined: {}", name)
+            }
+
+            Self::DuplicateVar { name } => {
+                write!(f, "var already declared: {}", name)
+            }
+
+            Self::UndefinedFn {
+                name,
+                suggestion: Some(suggestion),
+            } => {
+                write!(
+                    f,
+                    "fn not defined: {} (did you mean `{}`?)",
+                    name, suggestion
+                )
+            }
+
+            Self::UndefinedFn {
+                name,
+                suggestion: None,
+            } => {
+                write!(f, "fn not defined: {}", name)
+            }
+
+            Self::DuplicateFn { name } => {
+                write!(f, "fn already declared: {}", name)
+            }
+
+            Self::ArgCountMismatch {
+                name,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "fn {} takes {} argument(s), but {} were given",
+                    name, expected, found
+                )
+            }
+
+            Self::TypeMismatch { expected, found } => {
+                write!(f, "type mismatch: expected {}, found {}", expected, found)
+            }
+
+            Self::SignatureMismatch {
+                declared,
+                inferred,
+                path,
+            } => {
+                write!(
+                    f,
+                    "program is declared to return {}, but returns {} (at {})",
+                    declared, inferred, path
+                )
+            }
+
+            Self::RustTypeMismatch { declared, rust } => {
+                write!(
+                    f,
+                    "program is declared with type {}, but used with Rust type of {}",
+                    declared, rust
+                )
+            }
+
+            Self::UnsupportedOp { op, lhs, rhs } => {
+                write!(f, "unsupported operands for {:?}: {} and {}", op, lhs, rhs)
+            }
+
+            Self::EmptyBlock => {
+                write!(f, "empty block")
+            }
+
+            Self::MissingStateStore => {
+                write!(f, "program accesses state, but no state store was provided")
+            }
+
+            Self::UnfilledHole { id } => {
+                write!(f, "unfilled hole: {}", id)
+            }
+
+            Self::FillTypeMismatch {
+                id,
+                expected,
+                found,
+            } => {
+                write!(f, "hole {} expects {}, got {}", id, expected, found)
+            }
+        }
+    }
+}
+
+impl Error for CompileError {}
@@ -0,0 +1,331 @@
+//! Ready-made programs, useful for smoke-testing and benchmarking.
+
+use crate::{Node, Program, Type, Value};
+
+/// Returns all programs from the corpus, along with their names.
+pub fn all() -> Vec<(&'static str, Program)> {
+    vec![
+        ("fib", fib()),
+        ("triangular", triangular()),
+        ("pow2", pow2()),
+        ("abs", abs()),
+        ("fib_recursive", fib_recursive()),
+        ("gcd", gcd()),
+    ]
+}
+
+/// Computes `input`-th Fibonacci number.
+pub fn fib() -> Program {
+    Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(vec![
+            // let x = 0
+            Node::Let {
+                name: "x",
+                value: Box::new(Node::Const(Value::Int(0))),
+            },
+            // let y = 1
+            Node::Let {
+                name: "y",
+                value: Box::new(Node::Const(Value::Int(1))),
+            },
+            // let z = 1
+            Node::Let {
+                name: "z",
+                value: Box::new(Node::Const(Value::Int(1))),
+            },
+            // let n = input
+            Node::Let {
+                name: "n",
+                value: Box::new(Node::Var("input")),
+            },
+            // while n > 0
+            Node::While {
+                cond: Box::new(Node::Gt {
+                    lhs: Box::new(Node::Var("n")),
+                    rhs: Box::new(Node::Const(Value::Int(0))),
+                }),
+                body: Box::new(Node::Block(vec![
+                    // x = y
+                    Node::Assign {
+                        name: "x",
+                        value: Box::new(Node::Var("y")),
+                    },
+                    // y = z
+                    Node::Assign {
+                        name: "y",
+                        value: Box::new(Node::Var("z")),
+                    },
+                    // z = x + y
+                    Node::Assign {
+                        name: "z",
+                        value: Box::new(Node::Add {
+                            lhs: Box::new(Node::Var("x")),
+                            rhs: Box::new(Node::Var("y")),
+                        }),
+                    },
+                    // n = n - 1
+                    Node::Assign {
+                        name: "n",
+                        value: Box::new(Node::Sub {
+                            lhs: Box::new(Node::Var("n")),
+                            rhs: Box::new(Node::Const(Value::Int(1))),
+                        }),
+                    },
+                ])),
+            },
+            // x
+            Node::Var("x"),
+        ]),
+    }
+}
+
+/// Computes `1 + 2 + ... + input`.
+pub fn triangular() -> Program {
+    Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(vec![
+            // let sum = 0
+            Node::Let {
+                name: "sum",
+                value: Box::new(Node::Const(Value::Int(0))),
+            },
+            // let n = input
+            Node::Let {
+                name: "n",
+                value: Box::new(Node::Var("input")),
+            },
+            // while n > 0
+            Node::While {
+                cond: Box::new(Node::Gt {
+                    lhs: Box::new(Node::Var("n")),
+                    rhs: Box::new(Node::Const(Value::Int(0))),
+                }),
+                body: Box::new(Node::Block(vec![
+                    // sum = sum + n
+                    Node::Assign {
+                        name: "sum",
+                        value: Box::new(Node::Add {
+                            lhs: Box::new(Node::Var("sum")),
+                            rhs: Box::new(Node::Var("n")),
+                        }),
+                    },
+                    // n = n - 1
+                    Node::Assign {
+                        name: "n",
+                        value: Box::new(Node::Sub {
+                            lhs: Box::new(Node::Var("n")),
+                            rhs: Box::new(Node::Const(Value::Int(1))),
+                        }),
+                    },
+                ])),
+            },
+            // sum
+            Node::Var("sum"),
+        ]),
+    }
+}
+
+/// Computes `2 ^ input`, by repeated doubling.
+pub fn pow2() -> Program {
+    Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(vec![
+            // let x = 1
+            Node::Let {
+                name: "x",
+                value: Box::new(Node::Const(Value::Int(1))),
+            },
+            // let n = input
+            Node::Let {
+                name: "n",
+                value: Box::new(Node::Var("input")),
+            },
+            // while n > 0
+            Node::While {
+                cond: Box::new(Node::Gt {
+                    lhs: Box::new(Node::Var("n")),
+                    rhs: Box::new(Node::Const(Value::Int(0))),
+                }),
+                body: Box::new(Node::Block(vec![
+                    // x = x + x
+                    Node::Assign {
+                        name: "x",
+                        value: Box::new(Node::Add {
+                            lhs: Box::new(Node::Var("x")),
+                            rhs: Box::new(Node::Var("x")),
+                        }),
+                    },
+                    // n = n - 1
+                    Node::Assign {
+                        name: "n",
+                        value: Box::new(Node::Sub {
+                            lhs: Box::new(Node::Var("n")),
+                            rhs: Box::new(Node::Const(Value::Int(1))),
+                        }),
+                    },
+                ])),
+            },
+            // x
+            Node::Var("x"),
+        ]),
+    }
+}
+
+/// Computes the absolute value of `input`.
+pub fn abs() -> Program {
+    Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        // if 0 > input { 0 - input } else { input }
+        body: Node::If {
+            cond: Box::new(Node::Gt {
+                lhs: Box::new(Node::Const(Value::Int(0))),
+                rhs: Box::new(Node::Var("input")),
+            }),
+            then: Box::new(Node::Sub {
+                lhs: Box::new(Node::Const(Value::Int(0))),
+                rhs: Box::new(Node::Var("input")),
+            }),
+            else_: Some(Box::new(Node::Var("input"))),
+        },
+    }
+}
+
+/// Computes `input`-th Fibonacci number recursively.
+pub fn fib_recursive() -> Program {
+    Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(vec![
+            // fn fib(n: int) -> int
+            Node::Fn {
+                name: "fib",
+                params: vec![("n", Type::Int)],
+                output: Type::Int,
+                // if 2 > n { n } else { fib(n - 1) + fib(n - 2) }
+                body: Box::new(Node::If {
+                    cond: Box::new(Node::Gt {
+                        lhs: Box::new(Node::Const(Value::Int(2))),
+                        rhs: Box::new(Node::Var("n")),
+                    }),
+                    then: Box::new(Node::Var("n")),
+                    else_: Some(Box::new(Node::Add {
+                        lhs: Box::new(Node::Call {
+                            name: "fib",
+                            args: vec![Node::Sub {
+                                lhs: Box::new(Node::Var("n")),
+                                rhs: Box::new(Node::Const(Value::Int(1))),
+                            }],
+                        }),
+                        rhs: Box::new(Node::Call {
+                            name: "fib",
+                            args: vec![Node::Sub {
+                                lhs: Box::new(Node::Var("n")),
+                                rhs: Box::new(Node::Const(Value::Int(2))),
+                            }],
+                        }),
+                    })),
+                }),
+            },
+            // fib(input)
+            Node::Call {
+                name: "fib",
+                args: vec![Node::Var("input")],
+            },
+        ]),
+    }
+}
+
+/// Computes the greatest common divisor of `input` and 60 by repeated
+/// subtraction (inputs below 1 are treated as 60).
+pub fn gcd() -> Program {
+    Program {
+        input: Type::Int,
+        output: Some(Type::Int),
+        body: Node::Block(vec![
+            // let a = input
+            Node::Let {
+                name: "a",
+                value: Box::new(Node::Var("input")),
+            },
+            // let b = 60
+            Node::Let {
+                name: "b",
+                value: Box::new(Node::Const(Value::Int(60))),
+            },
+            // if 1 > a { a = b }
+            Node::If {
+                cond: Box::new(Node::Gt {
+                    lhs: Box::new(Node::Const(Value::Int(1))),
+                    rhs: Box::new(Node::Var("a")),
+                }),
+                then: Box::new(Node::Assign {
+                    name: "a",
+                    value: Box::new(Node::Var("b")),
+                }),
+                else_: None,
+            },
+            // while a != b
+            Node::While {
+                cond: Box::new(Node::Ne {
+                    lhs: Box::new(Node::Var("a")),
+                    rhs: Box::new(Node::Var("b")),
+                }),
+                // if a > b { a = a - b } else { b = b - a }
+                body: Box::new(Node::If {
+                    cond: Box::new(Node::Gt {
+                        lhs: Box::new(Node::Var("a")),
+                        rhs: Box::new(Node::Var("b")),
+                    }),
+                    then: Box::new(Node::Assign {
+                        name: "a",
+                        value: Box::new(Node::Sub {
+                            lhs: Box::new(Node::Var("a")),
+                            rhs: Box::new(Node::Var("b")),
+                        }),
+                    }),
+                    else_: Some(Box::new(Node::Assign {
+                        name: "b",
+                        value: Box::new(Node::Sub {
+                            lhs: Box::new(Node::Var("b")),
+                            rhs: Box::new(Node::Var("a")),
+                        }),
+                    })),
+                }),
+            },
+            // a
+            Node::Var("a"),
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, TypedProgram};
+
+    #[test]
+    fn all_compile() {
+        for (name, prog) in all() {
+            if let Err(err) = TypedProgram::<i32, i32>::new(prog).and_then(compile) {
+                panic!("{} doesn't compile: {}", name, err);
+            }
+        }
+    }
+
+    #[test]
+    fn gcd() {
+        let f = TypedProgram::<i32, i32>::new(super::gcd())
+            .and_then(compile)
+            .unwrap();
+
+        assert_eq!(60, f(0));
+        assert_eq!(10, f(10));
+        assert_eq!(5, f(25));
+        assert_eq!(1, f(7));
+    }
+}
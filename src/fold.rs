@@ -0,0 +1,191 @@
+use crate::Node;
+
+/// Replaces subtrees consisting only of constants with their values, so
+/// that they're computed once, during compilation, instead of on each run.
+///
+/// Subtrees whose evaluation would fail (e.g. overflow) are left as they
+/// are, so that they keep failing at runtime.
+pub(crate) fn fold_consts(node: Node) -> Node {
+    let node = match node {
+        Node::Let { name, value } => Node::Let {
+            name,
+            value: Box::new(fold_consts(*value)),
+        },
+
+        Node::Assign { name, value } => Node::Assign {
+            name,
+            value: Box::new(fold_consts(*value)),
+        },
+
+        Node::Const(_) | Node::Var(_) | Node::Hole(..) => {
+            return node;
+        }
+
+        Node::Gt { lhs, rhs } => Node::Gt {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Lt { lhs, rhs } => Node::Lt {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Ge { lhs, rhs } => Node::Ge {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Le { lhs, rhs } => Node::Le {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Eq { lhs, rhs } => Node::Eq {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Ne { lhs, rhs } => Node::Ne {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Add { lhs, rhs } => Node::Add {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::Sub { lhs, rhs } => Node::Sub {
+            lhs: Box::new(fold_consts(*lhs)),
+            rhs: Box::new(fold_consts(*rhs)),
+        },
+
+        Node::While { cond, body } => Node::While {
+            cond: Box::new(fold_consts(*cond)),
+            body: Box::new(fold_consts(*body)),
+        },
+
+        Node::If { cond, then, else_ } => Node::If {
+            cond: Box::new(fold_consts(*cond)),
+            then: Box::new(fold_consts(*then)),
+            else_: else_.map(|else_| Box::new(fold_consts(*else_))),
+        },
+
+        Node::Fn {
+            name,
+            params,
+            output,
+            body,
+        } => Node::Fn {
+            name,
+            params,
+            output,
+            body: Box::new(fold_consts(*body)),
+        },
+
+        Node::Call { name, args } => Node::Call {
+            name,
+            args: args.into_iter().map(fold_consts).collect(),
+        },
+
+        Node::LoadState { key, default } => Node::LoadState {
+            key,
+            default: Box::new(fold_consts(*default)),
+        },
+
+        Node::SaveState { key, value } => Node::SaveState {
+            key,
+            value: Box::new(fold_consts(*value)),
+        },
+
+        Node::Block(nodes) => Node::Block(nodes.into_iter().map(fold_consts).collect()),
+    };
+
+    match node.eval_const() {
+        Some(value) => Node::Const(value),
+        None => node,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compile_with, parse, CompileError, CompileOptions, NodeKind, Type, TypedProgram, Value,
+    };
+    use std::cmp::Ordering;
+
+    /// Checks that the comparison evaluates to `expected` both when folded
+    /// and when evaluated at runtime.
+    fn check(src: String, expected: bool) {
+        let prog = parse(Box::leak(src.clone().into_boxed_str())).unwrap();
+
+        assert_eq!(
+            Node::Block(vec![Node::Const(Value::Bool(expected))]),
+            fold_consts(prog.body.clone()),
+            "{}",
+            src,
+        );
+
+        let opts = CompileOptions {
+            disable_optimizations: true,
+            ..Default::default()
+        };
+
+        let f = TypedProgram::<i32, bool>::new(prog)
+            .and_then(|prog| compile_with(prog, &opts))
+            .unwrap();
+
+        assert_eq!(expected, f(0), "{}", src);
+    }
+
+    #[test]
+    fn comparisons() {
+        let ops = [
+            (">", Ordering::is_gt as fn(Ordering) -> bool),
+            ("<", Ordering::is_lt),
+            (">=", Ordering::is_ge),
+            ("<=", Ordering::is_le),
+            ("==", Ordering::is_eq),
+            ("!=", Ordering::is_ne),
+        ];
+
+        for (op, expected) in ops {
+            for lhs in -1..=1 {
+                for rhs in -1..=1 {
+                    check(format!("{} {} {}", lhs, op, rhs), expected(lhs.cmp(&rhs)));
+
+                    check(
+                        format!("{}.5 {} {}.5", lhs, op, rhs),
+                        expected(lhs.cmp(&rhs)),
+                    );
+                }
+            }
+        }
+
+        for lhs in [false, true] {
+            for rhs in [false, true] {
+                check(format!("{} == {}", lhs, rhs), lhs == rhs);
+                check(format!("{} != {}", lhs, rhs), lhs != rhs);
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_comparisons() {
+        for (op, kind) in [("<", NodeKind::Lt), (">=", NodeKind::Ge)] {
+            let src = Box::leak(format!("true {} false", op).into_boxed_str());
+
+            assert!(matches!(
+                TypedProgram::<i32, bool>::new(parse(src).unwrap())
+                    .and_then(|prog| compile_with(prog, &CompileOptions::default())),
+                Err(CompileError::UnsupportedOp {
+                    op,
+                    lhs: Type::Bool,
+                    rhs: Type::Bool,
+                }) if op == kind,
+            ));
+        }
+    }
+}
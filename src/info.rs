@@ -0,0 +1,149 @@
+use crate::{Node, NodeKind, Program};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramInfo {
+    /// Number of nodes of each kind
+    pub nodes: BTreeMap<NodeKind, usize>,
+
+    /// Length of the longest path from the body to a leaf node
+    pub max_depth: usize,
+
+    /// Number of `while` loops
+    pub loops: usize,
+
+    /// Maximum number of `while` loops nested within each other
+    pub max_loop_nesting: usize,
+
+    /// Number of variables declared by the program (excluding `input`)
+    pub vars: usize,
+
+    /// Number of stack slots the compiled program will allocate per run,
+    /// excluding frames of function calls
+    pub stack_size: usize,
+}
+
+impl Program {
+    pub fn info(&self) -> ProgramInfo {
+        let mut info = ProgramInfo::default();
+
+        visit(&mut info, &self.body, 1, 0);
+
+        info.loops = info.nodes.get(&NodeKind::While).copied().unwrap_or(0);
+        info.vars = info.nodes.get(&NodeKind::Let).copied().unwrap_or(0);
+        info.stack_size = 1 + frame_slots(&self.body).0;
+        info
+    }
+}
+
+/// Returns the number of stack slots needed to evaluate `node` (excluding
+/// frames of function calls) and the number of slots that remain in use
+/// afterwards, i.e. ones of its `let`s that aren't enclosed in a block.
+fn frame_slots(node: &Node) -> (usize, usize) {
+    if let Node::Fn { .. } = node {
+        return (0, 0);
+    }
+
+    let mut peak = 0;
+    let mut live = 0;
+
+    for (idx, child) in node.children().into_iter().enumerate() {
+        let (child_peak, child_live) = frame_slots(child);
+
+        peak = peak.max(live + child_peak);
+
+        // Slots of branches' variables get reused once they end, like those
+        // of blocks
+        if !node.is_branch(idx) {
+            live += child_live;
+        }
+    }
+
+    match node {
+        Node::Let { .. } => {
+            live += 1;
+            peak = peak.max(live);
+        }
+
+        // Slots of variables declared within the block get reused once it
+        // ends
+        Node::Block(_) => {
+            live = 0;
+        }
+
+        _ => {}
+    }
+
+    (peak, live)
+}
+
+fn visit(info: &mut ProgramInfo, node: &Node, depth: usize, mut loop_nesting: usize) {
+    *info.nodes.entry(node.kind()).or_default() += 1;
+    info.max_depth = info.max_depth.max(depth);
+
+    if let Node::While { .. } = node {
+        loop_nesting += 1;
+        info.max_loop_nesting = info.max_loop_nesting.max(loop_nesting);
+    }
+
+    for child in node.children() {
+        visit(info, child, depth + 1, loop_nesting);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile_program, parse, CompileOptions};
+
+    #[test]
+    fn info() {
+        let prog =
+            parse("let x = 1; while x > 0 { let y = x; while y > 0 { y = y - 1 }; x = x - 1 }; x")
+                .unwrap();
+
+        let nodes = [
+            (NodeKind::Const, 5),
+            (NodeKind::Var, 6),
+            (NodeKind::Let, 2),
+            (NodeKind::Assign, 2),
+            (NodeKind::Gt, 2),
+            (NodeKind::Sub, 2),
+            (NodeKind::While, 2),
+            (NodeKind::Block, 3),
+        ];
+
+        assert_eq!(
+            ProgramInfo {
+                nodes: nodes.into_iter().collect(),
+                max_depth: 8,
+                loops: 2,
+                max_loop_nesting: 2,
+                vars: 2,
+                stack_size: 3,
+            },
+            prog.info(),
+        );
+    }
+
+    #[test]
+    fn stack_size() {
+        let progs = [
+            "input",
+            "let x = 1; let y = 2; x",
+            "{ let x = 1; x }; let y = 2; y",
+            "if input > 0 { let x = 1; x } else { let y = 2; let z = 3; z }",
+            "let x = 1; while x > 0 { let y = x; x = y - 1 }; x",
+            "fn f(n: int) -> int { let m = n; let k = m; k }; let x = 1; f(x)",
+            "let x = { let y = 1; let z = 2; y }; x",
+        ];
+
+        for src in progs {
+            let prog = parse(src).unwrap();
+            let stack_size = prog.info().stack_size;
+            let compiled = compile_program(prog, None, &CompileOptions::default()).unwrap();
+
+            assert_eq!(compiled.stack_len, stack_size, "{}", src);
+        }
+    }
+}
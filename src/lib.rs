@@ -0,0 +1,2104 @@
+pub use self::cost::*;
+pub use self::diff::*;
+pub use self::equivalence::*;
+pub use self::error::*;
+pub use self::info::*;
+pub use self::lint::*;
+pub use self::metrics::*;
+pub use self::mutation::*;
+pub use self::parser::*;
+pub use self::path::*;
+pub use self::ruleset::*;
+pub use self::state::*;
+pub use self::termination::*;
+pub use self::trace::*;
+pub use self::well_formed::*;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub mod examples_corpus;
+
+mod cost;
+mod diff;
+mod equivalence;
+mod error;
+mod fold;
+mod info;
+mod lint;
+mod metrics;
+mod mutation;
+mod parser;
+mod path;
+mod ruleset;
+mod state;
+mod template;
+mod termination;
+mod trace;
+mod well_formed;
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub input: Type,
+
+    /// When `None`, the output type is inferred from the body
+    pub output: Option<Type>,
+
+    pub body: Node,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Unit,
+    Bool,
+    Int,
+    Float,
+}
+
+impl Type {
+    /// Returns the type's name, as the surface language would spell it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unit => "unit",
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+        }
+    }
+
+    /// Returns whether the type supports arithmetic.
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Self::Int | Self::Float)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(i32),
+    Float(f64),
+}
+
+impl Value {
+    pub fn ty(&self) -> Type {
+        match self {
+            Self::Unit => Type::Unit,
+            Self::Bool(_) => Type::Bool,
+            Self::Int(_) => Type::Int,
+            Self::Float(_) => Type::Float,
+        }
+    }
+
+    pub fn unwrap_bool(self) -> bool {
+        if let Self::Bool(value) = self {
+            value
+        } else {
+            panic!("expected bool, got: {:?}", self);
+        }
+    }
+
+    pub fn unwrap_int(self) -> i32 {
+        if let Self::Int(value) = self {
+            value
+        } else {
+            panic!("expected int, got: {:?}", self);
+        }
+    }
+
+    pub fn unwrap_float(self) -> f64 {
+        if let Self::Float(value) = self {
+            value
+        } else {
+            panic!("expected float, got: {:?}", self);
+        }
+    }
+}
+
+/// Values are displayed the way [`parse()`] reads them back.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "()"),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Int(value) => write!(f, "{}", value),
+
+            Self::Float(value) if value.is_nan() => write!(f, "nan"),
+            Self::Float(value) if value.is_infinite() && *value > 0.0 => write!(f, "inf"),
+            Self::Float(value) if value.is_infinite() => write!(f, "-inf"),
+
+            // Unlike Debug, Display never switches to the exponent notation,
+            // which the parser doesn't support - but it omits the fractional
+            // part of whole numbers, which the parser requires
+            Self::Float(value) => {
+                let value = value.to_string();
+
+                if value.contains('.') {
+                    write!(f, "{}", value)
+                } else {
+                    write!(f, "{}.0", value)
+                }
+            }
+        }
+    }
+}
+
+/// Floats are compared by their bits, so that - unlike in IEEE 754 - `NaN`
+/// equals itself (and `0.0` doesn't equal `-0.0`), which keeps values (and
+/// nodes) proper `Eq`.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Unit, Self::Unit) => true,
+            (Self::Bool(lhs), Self::Bool(rhs)) => lhs == rhs,
+            (Self::Int(lhs), Self::Int(rhs)) => lhs == rhs,
+            (Self::Float(lhs), Self::Float(rhs)) => lhs.to_bits() == rhs.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            Self::Unit => {}
+            Self::Bool(value) => value.hash(state),
+            Self::Int(value) => value.hash(state),
+            Self::Float(value) => value.to_bits().hash(state),
+        }
+    }
+}
+
+/// Values of the same type are ordered as their Rust counterparts are (floats
+/// through [`f64::total_cmp()`], consistently with their equality); values of
+/// different types are incomparable.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Unit, Self::Unit) => Some(Ordering::Equal),
+            (Self::Bool(lhs), Self::Bool(rhs)) => lhs.partial_cmp(rhs),
+            (Self::Int(lhs), Self::Int(rhs)) => lhs.partial_cmp(rhs),
+            (Self::Float(lhs), Self::Float(rhs)) => Some(lhs.total_cmp(rhs)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// let name = value
+    Let {
+        name: &'static str,
+        value: Box<Self>,
+    },
+
+    /// name = value
+    Assign {
+        name: &'static str,
+        value: Box<Self>,
+    },
+
+    /// E.g. 123
+    Const(Value),
+
+    /// E.g. foo
+    Var(&'static str),
+
+    /// lhs > rhs
+    Gt { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs < rhs
+    Lt { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs >= rhs
+    Ge { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs <= rhs
+    Le { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs == rhs
+    Eq { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs != rhs
+    Ne { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs + rhs
+    Add { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// lhs - rhs
+    Sub { lhs: Box<Self>, rhs: Box<Self> },
+
+    /// while cond { body }
+    While { cond: Box<Self>, body: Box<Self> },
+
+    /// if cond { then } else { else_ }
+    ///
+    /// Without `else_`, the node evaluates to `()`.
+    If {
+        cond: Box<Self>,
+        then: Box<Self>,
+        else_: Option<Box<Self>>,
+    },
+
+    /// fn name(params) -> output { body }
+    ///
+    /// Functions run in stack frames of their own, seeing only their
+    /// parameters, and can be called anywhere after their declaration
+    /// (including their own body).
+    Fn {
+        name: &'static str,
+        params: Vec<(&'static str, Type)>,
+        output: Type,
+        body: Box<Self>,
+    },
+
+    /// name(args)
+    ///
+    /// Recursion is limited by [`MAX_CALL_DEPTH`], which counts the depths of
+    /// the called functions' bodies rather than the calls themselves - e.g. a
+    /// function whose body is 20 levels deep can only recurse about
+    /// `MAX_CALL_DEPTH / 20` (i.e. 102) times before the program panics.
+    Call { name: &'static str, args: Vec<Self> },
+
+    /// load_state("key", default)
+    ///
+    /// Evaluates to the value stored under `key` in the program's
+    /// [`StateStore`], or to `default` if there's none - or if the stored
+    /// value is of another type than `default`.
+    LoadState {
+        key: &'static str,
+        default: Box<Self>,
+    },
+
+    /// save_state("key", value)
+    SaveState { key: &'static str, value: Box<Self> },
+
+    /// { ... }
+    Block(Vec<Self>),
+
+    /// Placeholder for a subtree of given type, filled in by
+    /// [`Program::instantiate()`]
+    Hole(usize, Type),
+}
+
+impl Node {
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            Self::Let { .. } => NodeKind::Let,
+            Self::Assign { .. } => NodeKind::Assign,
+            Self::Const(_) => NodeKind::Const,
+            Self::Var(_) => NodeKind::Var,
+            Self::Gt { .. } => NodeKind::Gt,
+            Self::Lt { .. } => NodeKind::Lt,
+            Self::Ge { .. } => NodeKind::Ge,
+            Self::Le { .. } => NodeKind::Le,
+            Self::Eq { .. } => NodeKind::Eq,
+            Self::Ne { .. } => NodeKind::Ne,
+            Self::Add { .. } => NodeKind::Add,
+            Self::Sub { .. } => NodeKind::Sub,
+            Self::While { .. } => NodeKind::While,
+            Self::If { .. } => NodeKind::If,
+            Self::Fn { .. } => NodeKind::Fn,
+            Self::Call { .. } => NodeKind::Call,
+            Self::LoadState { .. } => NodeKind::LoadState,
+            Self::SaveState { .. } => NodeKind::SaveState,
+            Self::Block(_) => NodeKind::Block,
+            Self::Hole(..) => NodeKind::Hole,
+        }
+    }
+
+    /// Evaluates this node if it consists only of constants.
+    pub fn eval_const(&self) -> Option<Value> {
+        match self {
+            Self::Const(value) => Some(value.clone()),
+
+            Self::Gt { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Int(lhs), Value::Int(rhs)) => Some(Value::Bool(lhs > rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Bool(lhs > rhs)),
+                _ => None,
+            },
+
+            Self::Lt { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Int(lhs), Value::Int(rhs)) => Some(Value::Bool(lhs < rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Bool(lhs < rhs)),
+                _ => None,
+            },
+
+            Self::Ge { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Int(lhs), Value::Int(rhs)) => Some(Value::Bool(lhs >= rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Bool(lhs >= rhs)),
+                _ => None,
+            },
+
+            Self::Le { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Int(lhs), Value::Int(rhs)) => Some(Value::Bool(lhs <= rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Bool(lhs <= rhs)),
+                _ => None,
+            },
+
+            Self::Eq { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Bool(lhs), Value::Bool(rhs)) => Some(Value::Bool(lhs == rhs)),
+                (Value::Int(lhs), Value::Int(rhs)) => Some(Value::Bool(lhs == rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Bool(lhs == rhs)),
+                _ => None,
+            },
+
+            Self::Ne { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Bool(lhs), Value::Bool(rhs)) => Some(Value::Bool(lhs != rhs)),
+                (Value::Int(lhs), Value::Int(rhs)) => Some(Value::Bool(lhs != rhs)),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Bool(lhs != rhs)),
+                _ => None,
+            },
+
+            Self::Add { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Int(lhs), Value::Int(rhs)) => lhs.checked_add(rhs).map(Value::Int),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Float(lhs + rhs)),
+                _ => None,
+            },
+
+            Self::Sub { lhs, rhs } => match (lhs.eval_const()?, rhs.eval_const()?) {
+                (Value::Int(lhs), Value::Int(rhs)) => lhs.checked_sub(rhs).map(Value::Int),
+                (Value::Float(lhs), Value::Float(rhs)) => Some(Value::Float(lhs - rhs)),
+                _ => None,
+            },
+
+            // Both branches have to be constant, so that folding doesn't drop
+            // any `let`s from the branch that's not taken - and of the same
+            // type, so that it doesn't drop a type error either
+            Self::If { cond, then, else_ } => {
+                let then = then.eval_const()?;
+
+                let (then, else_) = match else_ {
+                    Some(else_) => (then, else_.eval_const()?),
+                    None => (Value::Unit, Value::Unit),
+                };
+
+                if then.ty() != else_.ty() {
+                    return None;
+                }
+
+                match cond.eval_const()? {
+                    Value::Bool(true) => Some(then),
+                    Value::Bool(false) => Some(else_),
+                    _ => None,
+                }
+            }
+
+            _ => None,
+        }
+    }
+
+    pub fn children(&self) -> Vec<&Self> {
+        match self {
+            Self::Let { value, .. }
+            | Self::Assign { value, .. }
+            | Self::LoadState { default: value, .. }
+            | Self::SaveState { value, .. } => vec![value],
+            Self::Const(_) | Self::Var(_) | Self::Hole(..) => vec![],
+            Self::Gt { lhs, rhs }
+            | Self::Lt { lhs, rhs }
+            | Self::Ge { lhs, rhs }
+            | Self::Le { lhs, rhs }
+            | Self::Eq { lhs, rhs }
+            | Self::Ne { lhs, rhs }
+            | Self::Add { lhs, rhs }
+            | Self::Sub { lhs, rhs } => vec![lhs, rhs],
+            Self::While { cond, body } => vec![cond, body],
+            Self::If { cond, then, else_ } => {
+                let mut children: Vec<&Self> = vec![cond, then];
+
+                children.extend(else_.as_deref());
+                children
+            }
+            Self::Fn { body, .. } => vec![body],
+            Self::Call { args, .. } => args.iter().collect(),
+            Self::Block(nodes) => nodes.iter().collect(),
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Self> {
+        match self {
+            Self::Let { value, .. }
+            | Self::Assign { value, .. }
+            | Self::LoadState { default: value, .. }
+            | Self::SaveState { value, .. } => vec![value],
+            Self::Const(_) | Self::Var(_) | Self::Hole(..) => vec![],
+            Self::Gt { lhs, rhs }
+            | Self::Lt { lhs, rhs }
+            | Self::Ge { lhs, rhs }
+            | Self::Le { lhs, rhs }
+            | Self::Eq { lhs, rhs }
+            | Self::Ne { lhs, rhs }
+            | Self::Add { lhs, rhs }
+            | Self::Sub { lhs, rhs } => vec![lhs, rhs],
+            Self::While { cond, body } => vec![cond, body],
+            Self::If { cond, then, else_ } => {
+                let mut children: Vec<&mut Self> = vec![cond, then];
+
+                children.extend(else_.as_deref_mut());
+                children
+            }
+            Self::Fn { body, .. } => vec![body],
+            Self::Call { args, .. } => args.iter_mut().collect(),
+            Self::Block(nodes) => nodes.iter_mut().collect(),
+        }
+    }
+
+    /// Returns whether the `idx`-th child (in the order of [`Self::children()`])
+    /// might not run when this node does, i.e. is a branch of an `if`, a
+    /// loop's body or a default of [`Self::LoadState`].
+    ///
+    /// Like blocks, branches get scopes of their own, so a `let` they consist
+    /// of goes out of scope once they're evaluated.
+    pub(crate) fn is_branch(&self, idx: usize) -> bool {
+        matches!(
+            (self, idx),
+            (Self::While { .. }, 1) | (Self::If { .. }, 1 | 2) | (Self::LoadState { .. }, 0)
+        )
+    }
+
+    /// Returns the number of nodes in this subtree, including this node.
+    pub fn size(&self) -> usize {
+        1 + self.children().into_iter().map(Self::size).sum::<usize>()
+    }
+
+    /// Returns the length of the longest path from this node to a leaf.
+    ///
+    /// Unlike most other traversals, this one doesn't recurse on the Rust
+    /// stack, so it's safe to call on arbitrarily deep trees.
+    pub fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut pending = vec![(self, 1)];
+
+        while let Some((node, depth)) = pending.pop() {
+            max_depth = max_depth.max(depth);
+
+            for child in node.children() {
+                pending.push((child, depth + 1));
+            }
+        }
+
+        max_depth
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NodeKind {
+    Let,
+    Assign,
+    Const,
+    Var,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    Ne,
+    Add,
+    Sub,
+    While,
+    If,
+    Fn,
+    Call,
+    LoadState,
+    SaveState,
+    Block,
+    Hole,
+}
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+    fn ty() -> Type;
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+
+    fn ty() -> Type {
+        Type::Bool
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+
+    fn ty() -> Type {
+        Type::Int
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+
+    fn ty() -> Type {
+        Type::Float
+    }
+}
+
+pub trait FromValue {
+    fn from_value(value: Value) -> Self;
+    fn ty() -> Type;
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Self {
+        value.unwrap_bool()
+    }
+
+    fn ty() -> Type {
+        Type::Bool
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: Value) -> Self {
+        value.unwrap_int()
+    }
+
+    fn ty() -> Type {
+        Type::Int
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Self {
+        value.unwrap_float()
+    }
+
+    fn ty() -> Type {
+        Type::Float
+    }
+}
+
+/// Program whose signature has been checked against Rust types `Input` and
+/// `Output`
+#[derive(Debug)]
+pub struct TypedProgram<Input, Output> {
+    prog: Program,
+    _sig: PhantomData<fn(Input) -> Output>,
+}
+
+impl<Input, Output> TypedProgram<Input, Output>
+where
+    Input: IntoValue,
+    Output: FromValue,
+{
+    /// Fails with [`CompileError::RustTypeMismatch`] if the program's input
+    /// type (or its output type, if declared) differs from `Input` (or
+    /// `Output`).
+    pub fn new(prog: Program) -> Result<Self, CompileError> {
+        let sig = [(Some(prog.input), Input::ty()), (prog.output, Output::ty())];
+
+        for (declared, rust) in sig {
+            if let Some(declared) = declared {
+                if declared != rust {
+                    return Err(CompileError::RustTypeMismatch { declared, rust });
+                }
+            }
+        }
+
+        Ok(Self {
+            prog,
+            _sig: PhantomData,
+        })
+    }
+
+    pub fn into_inner(self) -> Program {
+        self.prog
+    }
+}
+
+impl<Input, Output> Deref for TypedProgram<Input, Output> {
+    type Target = Program;
+
+    fn deref(&self) -> &Self::Target {
+        &self.prog
+    }
+}
+
+/// Maximum nesting depth of a program accepted by [`parse()`] and
+/// [`compile()`], as measured by [`Node::depth()`]; when parsing, redundant
+/// parentheses count as a level, too.
+///
+/// Parsing and compilation recurse on the Rust stack, so without a limit a
+/// deeply nested (e.g. adversarial) program could overflow the host's stack.
+/// The limit is picked so that both fit in 2 MB (the default stack size of
+/// spawned threads) even in debug builds.
+pub const MAX_DEPTH: usize = 128;
+
+/// Maximum combined depth of the bodies of nested function calls; programs
+/// that recurse deeper panic.
+///
+/// Each call nests as many thunks on the Rust stack as its function's body
+/// is deep, so it's the calls' depth (not just their number) that's limited;
+/// this way an infinitely recursive program panics instead of overflowing the
+/// host's stack (which, unlike a panic, can't be recovered from). Together
+/// with [`MAX_DEPTH`], the limit is picked so that running a program (traced
+/// or not) fits in 2 MB of stack in debug builds.
+pub const MAX_CALL_DEPTH: usize = 2048;
+
+#[derive(Clone, Debug, Default)]
+pub struct CompileOptions {
+    /// Maximum number of nodes in the program
+    pub max_nodes: Option<usize>,
+
+    /// Maximum number of variables declared by the program (excluding
+    /// `input`)
+    pub max_vars: Option<usize>,
+
+    /// Maximum number of `while` loops nested within each other
+    pub max_loop_nesting: Option<usize>,
+
+    /// Maximum nesting depth of the program's nodes; regardless of this
+    /// option, programs deeper than [`MAX_DEPTH`] are always rejected
+    pub max_expression_depth: Option<usize>,
+
+    /// Whether to skip optimization passes (constant folding)
+    pub disable_optimizations: bool,
+
+    /// Sink notified about compilation and each run of the program
+    pub metrics: Option<Rc<dyn MetricsSink>>,
+
+    /// Store accessed by [`Node::LoadState`] and [`Node::SaveState`];
+    /// programs using either of them don't compile without one
+    pub state_store: Option<Rc<dyn StateStore>>,
+}
+
+pub fn compile<Input, Output>(
+    prog: TypedProgram<Input, Output>,
+) -> Result<impl Fn(Input) -> Output, CompileError>
+where
+    Input: IntoValue,
+    Output: FromValue,
+{
+    compile_with(prog, &CompileOptions::default())
+}
+
+pub fn compile_with<Input, Output>(
+    prog: TypedProgram<Input, Output>,
+    opts: &CompileOptions,
+) -> Result<impl Fn(Input) -> Output, CompileError>
+where
+    Input: IntoValue,
+    Output: FromValue,
+{
+    Ok(compile_with_report(prog, opts)?.0)
+}
+
+/// Statistics gathered while compiling a program
+#[derive(Clone, Debug)]
+pub struct CompileReport {
+    /// Time spent checking the program against limits
+    pub validate_time: Duration,
+
+    /// Time spent folding constants
+    pub optimize_time: Duration,
+
+    /// Time spent type-checking and generating thunks
+    pub codegen_time: Duration,
+
+    /// Number of nodes in the program as submitted
+    pub nodes_before: usize,
+
+    /// Number of nodes in the program after optimizations
+    pub nodes_after: usize,
+}
+
+pub fn compile_with_report<Input, Output>(
+    prog: TypedProgram<Input, Output>,
+    opts: &CompileOptions,
+) -> Result<(impl Fn(Input) -> Output, CompileReport), CompileError>
+where
+    Input: IntoValue,
+    Output: FromValue,
+{
+    let Compiled {
+        thunk,
+        stack_len,
+        report,
+        ..
+    } = compile_program(prog.into_inner(), Some(Output::ty()), opts)?;
+
+    let metrics = opts.metrics.clone();
+
+    if let Some(metrics) = &metrics {
+        metrics.on_compile(&report);
+    }
+
+    // Stack from the previous run, reused so that calling the program doesn't
+    // allocate
+    let spare_stack = Cell::new(None);
+
+    let prog = move |input: Input| -> Output {
+        let started_at = metrics.as_ref().map(|_| Instant::now());
+
+        let mut stack = spare_stack
+            .take()
+            .unwrap_or_else(|| vec![Value::Unit; stack_len]);
+
+        stack.fill(Value::Unit);
+        stack[0] = input.into_value();
+
+        let mut ctxt = RuntimeContext { stack, depth: 0 };
+        let output = thunk(&mut ctxt);
+
+        spare_stack.set(Some(ctxt.stack));
+
+        if let (Some(metrics), Some(started_at)) = (&metrics, started_at) {
+            metrics.on_run(started_at.elapsed());
+        }
+
+        Output::from_value(output)
+    };
+
+    Ok((prog, report))
+}
+
+struct Compiled {
+    output: Type,
+    thunk: Thunk,
+    stack_len: usize,
+    report: CompileReport,
+}
+
+/// Validates, optimizes and compiles given program into a thunk.
+///
+/// When `output` is `None`, the program's output type is inferred.
+fn compile_program(
+    prog: Program,
+    output: Option<Type>,
+    opts: &CompileOptions,
+) -> Result<Compiled, CompileError> {
+    compile_program_with_log(prog, output, opts, None)
+}
+
+/// Like [`compile_program()`], but when `log` is provided, the thunks record
+/// each evaluated node into it.
+fn compile_program_with_log(
+    prog: Program,
+    output: Option<Type>,
+    opts: &CompileOptions,
+    log: Option<trace::RawLog>,
+) -> Result<Compiled, CompileError> {
+    let started_at = Instant::now();
+    let depth = prog.body.depth();
+
+    if depth > MAX_DEPTH {
+        return Err(CompileError::TooDeep {
+            depth,
+            max: MAX_DEPTH,
+        });
+    }
+
+    let info = prog.info();
+    let nodes_before = prog.body.size();
+
+    if let Some(max_nodes) = opts.max_nodes {
+        if nodes_before > max_nodes {
+            return Err(CompileError::TooManyNodes {
+                nodes: nodes_before,
+                max: max_nodes,
+            });
+        }
+    }
+
+    if let Some(max_vars) = opts.max_vars {
+        if info.vars > max_vars {
+            return Err(CompileError::TooManyVars {
+                vars: info.vars,
+                max: max_vars,
+            });
+        }
+    }
+
+    if let Some(max_loop_nesting) = opts.max_loop_nesting {
+        if info.max_loop_nesting > max_loop_nesting {
+            return Err(CompileError::LoopsNestedTooDeeply {
+                nesting: info.max_loop_nesting,
+                max: max_loop_nesting,
+            });
+        }
+    }
+
+    if let Some(max_expression_depth) = opts.max_expression_depth {
+        if depth > max_expression_depth {
+            return Err(CompileError::TooDeep {
+                depth,
+                max: max_expression_depth,
+            });
+        }
+    }
+
+    let validated_at = Instant::now();
+    let output_path = prog.output_path();
+
+    let body = if opts.disable_optimizations {
+        prog.body
+    } else {
+        fold::fold_consts(prog.body)
+    };
+
+    let nodes_after = body.size();
+    let optimized_at = Instant::now();
+
+    let mut ctxt = CompilationContext::new(prog.input, log, opts.state_store.clone());
+
+    let (ty, thunk) = compile_node(&mut ctxt, body)?;
+
+    // Calls refer to the functions only weakly, so that recursive functions
+    // don't keep themselves alive - it's the program that owns them
+    let thunk: Thunk = if ctxt.fns.is_empty() {
+        thunk
+    } else {
+        let fns = ctxt.fn_table.clone();
+
+        Box::new(move |ctxt: &mut RuntimeContext| {
+            let _fns = &fns;
+
+            thunk(ctxt)
+        })
+    };
+
+    if let Some(output) = output.or(prog.output) {
+        if ty != output {
+            return Err(CompileError::SignatureMismatch {
+                declared: output,
+                inferred: ty,
+                path: output_path,
+            });
+        }
+    }
+
+    let report = CompileReport {
+        validate_time: validated_at - started_at,
+        optimize_time: optimized_at - validated_at,
+        codegen_time: optimized_at.elapsed(),
+        nodes_before,
+        nodes_after,
+    };
+
+    Ok(Compiled {
+        output: ty,
+        thunk,
+        stack_len: ctxt.frame_len,
+        report,
+    })
+}
+
+type Thunk = Box<dyn Fn(&mut RuntimeContext) -> Value>;
+
+struct CompilationContext {
+    /// Types of variables in scope, indexed by their stack slots
+    stack: Vec<Type>,
+
+    vars: HashMap<&'static str, usize>,
+
+    /// Variables shadowed (or, when `None`, introduced) by `let`s of each
+    /// block being compiled, restored once the block ends
+    scopes: Vec<Vec<(&'static str, Option<usize>)>>,
+
+    /// Number of stack slots the current frame needs, i.e. the maximum
+    /// length `stack` has reached - slots of variables whose blocks have ended
+    /// get reused by later ones
+    frame_len: usize,
+
+    /// Path of the node being compiled
+    path: NodePath,
+
+    /// Index of the next child to be compiled, for each node along `path`
+    next_child: Vec<usize>,
+
+    log: Option<trace::RawLog>,
+
+    /// Functions declared so far
+    fns: HashMap<&'static str, FnDecl>,
+
+    fn_table: Rc<FnTable>,
+
+    state_store: Option<Rc<dyn StateStore>>,
+
+    /// Types of holes filled by [`Program::instantiate()`], by their paths;
+    /// nodes at these paths have to be of their holes' types
+    fills: HashMap<NodePath, (usize, Type)>,
+}
+
+/// Function declared through [`Node::Fn`]
+struct FnDecl {
+    id: usize,
+    params: Vec<Type>,
+    output: Type,
+}
+
+struct CompiledFn {
+    thunk: Thunk,
+    frame_len: usize,
+
+    /// Nesting depth of the function's body, i.e. how many thunks a call
+    /// can nest on the Rust stack
+    depth: usize,
+}
+
+/// Compiled functions, indexed by their ids; each entry gets filled in once
+/// its function's body has been compiled, so that calls can refer to
+/// functions whose bodies are still being compiled (i.e. recursive calls)
+type FnTable = RefCell<Vec<Option<Rc<CompiledFn>>>>;
+
+impl CompilationContext {
+    fn new(
+        input: Type,
+        log: Option<trace::RawLog>,
+        state_store: Option<Rc<dyn StateStore>>,
+    ) -> Self {
+        Self {
+            stack: vec![input],
+            vars: FromIterator::from_iter(vec![("input", 0)]),
+            path: NodePath::root(),
+            next_child: Vec::new(),
+            log,
+            fns: HashMap::new(),
+            fn_table: Default::default(),
+            scopes: Vec::new(),
+            frame_len: 1,
+            state_store,
+            fills: HashMap::new(),
+        }
+    }
+
+    /// Opens a new scope, returning the number of stack slots in use before
+    /// it.
+    fn enter_scope(&mut self) -> usize {
+        self.scopes.push(Vec::new());
+        self.stack.len()
+    }
+
+    /// Closes the innermost scope, bringing back variables shadowed by its
+    /// `let`s and freeing their slots.
+    fn exit_scope(&mut self, slots: usize) {
+        for (name, shadowed) in self.scopes.pop().unwrap().into_iter().rev() {
+            match shadowed {
+                Some(id) => self.vars.insert(name, id),
+                None => self.vars.remove(name),
+            };
+        }
+
+        self.stack.truncate(slots);
+    }
+
+    fn resolve_var(&self, name: &'static str) -> Result<usize, CompileError> {
+        match self.vars.get(name) {
+            Some(id) => Ok(*id),
+
+            None => Err(CompileError::UndefinedVar {
+                name,
+                suggestion: suggest(name, self.vars.keys().copied()),
+            }),
+        }
+    }
+}
+
+/// Returns the closest-spelled candidate, if any is close enough to be a
+/// plausible typo.
+fn suggest(name: &str, candidates: impl Iterator<Item = &'static str>) -> Option<&'static str> {
+    let max_distance = (name.len() / 3).max(1);
+
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+struct RuntimeContext {
+    stack: Vec<Value>,
+
+    /// Combined depth of the bodies of function calls in progress
+    depth: usize,
+}
+
+fn compile_node(ctxt: &mut CompilationContext, node: Node) -> Result<(Type, Thunk), CompileError> {
+    // Nodes compile their children in the same order as Node::children()
+    // returns them, so the child's index is just the number of its siblings
+    // compiled so far
+    let idx = ctxt.next_child.last_mut().map(|next_child| {
+        *next_child += 1;
+        *next_child - 1
+    });
+
+    if let Some(idx) = idx {
+        ctxt.path.push(idx);
+    }
+
+    ctxt.next_child.push(0);
+
+    let kind = node.kind();
+    let (ty, thunk) = compile_node_inner(ctxt, node)?;
+
+    if let Some(&(id, expected)) = ctxt.fills.get(&ctxt.path) {
+        if ty != expected {
+            return Err(CompileError::FillTypeMismatch {
+                id,
+                expected,
+                found: ty,
+            });
+        }
+    }
+
+    ctxt.next_child.pop();
+
+    let thunk = match &ctxt.log {
+        Some(log) => {
+            let vars = ctxt.vars.iter().map(|(name, id)| (*name, *id)).collect();
+
+            trace::record(log.clone(), ctxt.path.clone(), kind, vars, thunk)
+        }
+        None => thunk,
+    };
+
+    if idx.is_some() {
+        ctxt.path.pop();
+    }
+
+    Ok((ty, thunk))
+}
+
+/// Compiles a branch (see [`Node::is_branch()`]) in a scope of its own, so
+/// that - even if it's not a block - its `let` doesn't outlive it.
+fn compile_scoped(
+    ctxt: &mut CompilationContext,
+    node: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let slots = ctxt.enter_scope();
+    let compiled = compile_node(ctxt, node)?;
+
+    ctxt.exit_scope(slots);
+
+    Ok(compiled)
+}
+
+fn compile_node_inner(
+    ctxt: &mut CompilationContext,
+    node: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    match node {
+        Node::Let { name, value } => compile_let(ctxt, name, *value),
+        Node::Assign { name, value } => compile_assign(ctxt, name, *value),
+
+        Node::Const(value) => {
+            let ty = value.ty();
+
+            let thunk = Box::new(move |_: &mut RuntimeContext| value.clone());
+
+            Ok((ty, thunk))
+        }
+
+        Node::Var(name) => {
+            let id = ctxt.resolve_var(name)?;
+
+            let ty = ctxt.stack[id];
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| ctxt.stack[id].clone());
+
+            Ok((ty, thunk))
+        }
+
+        Node::Gt { lhs, rhs } => compile_cmp(ctxt, NodeKind::Gt, *lhs, *rhs),
+        Node::Lt { lhs, rhs } => compile_cmp(ctxt, NodeKind::Lt, *lhs, *rhs),
+        Node::Ge { lhs, rhs } => compile_cmp(ctxt, NodeKind::Ge, *lhs, *rhs),
+        Node::Le { lhs, rhs } => compile_cmp(ctxt, NodeKind::Le, *lhs, *rhs),
+        Node::Eq { lhs, rhs } => compile_cmp(ctxt, NodeKind::Eq, *lhs, *rhs),
+        Node::Ne { lhs, rhs } => compile_cmp(ctxt, NodeKind::Ne, *lhs, *rhs),
+        Node::Add { lhs, rhs } => compile_add(ctxt, *lhs, *rhs),
+        Node::Sub { lhs, rhs } => compile_sub(ctxt, *lhs, *rhs),
+        Node::While { cond, body } => compile_while(ctxt, *cond, *body),
+        Node::If { cond, then, else_ } => compile_if(ctxt, *cond, *then, else_.map(|else_| *else_)),
+
+        Node::Fn {
+            name,
+            params,
+            output,
+            body,
+        } => compile_fn(ctxt, name, params, output, *body),
+
+        Node::Call { name, args } => compile_call(ctxt, name, args),
+        Node::LoadState { key, default } => compile_load_state(ctxt, key, *default),
+        Node::SaveState { key, value } => compile_save_state(ctxt, key, *value),
+        Node::Block(nodes) => compile_block(ctxt, nodes),
+        Node::Hole(id, _) => Err(CompileError::UnfilledHole { id }),
+    }
+}
+
+fn compile_let(
+    ctxt: &mut CompilationContext,
+    name: &'static str,
+    value: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let (ty, value) = compile_node(ctxt, value)?;
+    let id = ctxt.stack.len();
+
+    ctxt.stack.push(ty);
+    ctxt.frame_len = ctxt.frame_len.max(ctxt.stack.len());
+
+    let shadowed = ctxt.vars.insert(name, id);
+
+    if let Some(scope) = ctxt.scopes.last_mut() {
+        scope.push((name, shadowed));
+    }
+
+    let ty = Type::Unit;
+
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+        ctxt.stack[id] = value(ctxt);
+        Value::Unit
+    });
+
+    Ok((ty, thunk))
+}
+
+fn compile_assign(
+    ctxt: &mut CompilationContext,
+    name: &'static str,
+    value: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let id = ctxt.resolve_var(name)?;
+
+    let (ty, value) = compile_node(ctxt, value)?;
+
+    if ty != ctxt.stack[id] {
+        return Err(CompileError::TypeMismatch {
+            expected: ctxt.stack[id],
+            found: ty,
+        });
+    }
+
+    let ty = Type::Unit;
+
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+        ctxt.stack[id] = value(ctxt);
+        Value::Unit
+    });
+
+    Ok((ty, thunk))
+}
+
+fn compile_add(
+    ctxt: &mut CompilationContext,
+    lhs: Node,
+    rhs: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let (lhs_ty, lhs) = compile_node(ctxt, lhs)?;
+    let (rhs_ty, rhs) = compile_node(ctxt, rhs)?;
+
+    match (lhs_ty, rhs_ty) {
+        (Type::Int, Type::Int) => {
+            let ty = Type::Int;
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+                let lhs = lhs(ctxt).unwrap_int();
+                let rhs = rhs(ctxt).unwrap_int();
+
+                Value::Int(lhs + rhs)
+            });
+
+            Ok((ty, thunk))
+        }
+
+        (Type::Float, Type::Float) => {
+            let ty = Type::Float;
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+                let lhs = lhs(ctxt).unwrap_float();
+                let rhs = rhs(ctxt).unwrap_float();
+
+                Value::Float(lhs + rhs)
+            });
+
+            Ok((ty, thunk))
+        }
+
+        (lhs, rhs) => Err(CompileError::UnsupportedOp {
+            op: NodeKind::Add,
+            lhs,
+            rhs,
+        }),
+    }
+}
+
+fn compile_sub(
+    ctxt: &mut CompilationContext,
+    lhs: Node,
+    rhs: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let (lhs_ty, lhs) = compile_node(ctxt, lhs)?;
+    let (rhs_ty, rhs) = compile_node(ctxt, rhs)?;
+
+    match (lhs_ty, rhs_ty) {
+        (Type::Int, Type::Int) => {
+            let ty = Type::Int;
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+                let lhs = lhs(ctxt).unwrap_int();
+                let rhs = rhs(ctxt).unwrap_int();
+
+                Value::Int(lhs - rhs)
+            });
+
+            Ok((ty, thunk))
+        }
+
+        (Type::Float, Type::Float) => {
+            let ty = Type::Float;
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+                let lhs = lhs(ctxt).unwrap_float();
+                let rhs = rhs(ctxt).unwrap_float();
+
+                Value::Float(lhs - rhs)
+            });
+
+            Ok((ty, thunk))
+        }
+
+        (lhs, rhs) => Err(CompileError::UnsupportedOp {
+            op: NodeKind::Sub,
+            lhs,
+            rhs,
+        }),
+    }
+}
+
+fn compile_while(
+    ctxt: &mut CompilationContext,
+    cond: Node,
+    body: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let (cond_ty, cond) = compile_node(ctxt, cond)?;
+    let (_, body) = compile_scoped(ctxt, body)?;
+
+    if cond_ty != Type::Bool {
+        return Err(CompileError::TypeMismatch {
+            expected: Type::Bool,
+            found: cond_ty,
+        });
+    }
+
+    let ty = Type::Unit;
+
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+        while cond(ctxt).unwrap_bool() {
+            body(ctxt);
+        }
+
+        Value::Unit
+    });
+
+    Ok((ty, thunk))
+}
+
+fn compile_if(
+    ctxt: &mut CompilationContext,
+    cond: Node,
+    then: Node,
+    else_: Option<Node>,
+) -> Result<(Type, Thunk), CompileError> {
+    let (cond_ty, cond) = compile_node(ctxt, cond)?;
+    let (then_ty, then) = compile_scoped(ctxt, then)?;
+
+    if cond_ty != Type::Bool {
+        return Err(CompileError::TypeMismatch {
+            expected: Type::Bool,
+            found: cond_ty,
+        });
+    }
+
+    match else_ {
+        Some(else_) => {
+            let (else_ty, else_) = compile_scoped(ctxt, else_)?;
+
+            if then_ty != else_ty {
+                return Err(CompileError::TypeMismatch {
+                    expected: then_ty,
+                    found: else_ty,
+                });
+            }
+
+            let ty = then_ty;
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+                if cond(ctxt).unwrap_bool() {
+                    then(ctxt)
+                } else {
+                    else_(ctxt)
+                }
+            });
+
+            Ok((ty, thunk))
+        }
+
+        None => {
+            let ty = Type::Unit;
+
+            let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+                if cond(ctxt).unwrap_bool() {
+                    then(ctxt);
+                }
+
+                Value::Unit
+            });
+
+            Ok((ty, thunk))
+        }
+    }
+}
+
+fn compile_fn(
+    ctxt: &mut CompilationContext,
+    name: &'static str,
+    params: Vec<(&'static str, Type)>,
+    output: Type,
+    body: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    if ctxt.fns.contains_key(name) {
+        return Err(CompileError::DuplicateFn { name });
+    }
+
+    let id = ctxt.fns.len();
+    let depth = body.depth();
+    let param_tys: Vec<_> = params.iter().map(|(_, ty)| *ty).collect();
+    let mut vars = HashMap::new();
+
+    for (slot, (param, _)) in params.into_iter().enumerate() {
+        if vars.insert(param, slot).is_some() {
+            return Err(CompileError::DuplicateVar { name: param });
+        }
+    }
+
+    ctxt.fns.insert(
+        name,
+        FnDecl {
+            id,
+            params: param_tys.clone(),
+            output,
+        },
+    );
+
+    ctxt.fn_table.borrow_mut().push(None);
+
+    let frame_len = param_tys.len();
+    let outer_stack = mem::replace(&mut ctxt.stack, param_tys);
+    let outer_vars = mem::replace(&mut ctxt.vars, vars);
+    let outer_frame_len = mem::replace(&mut ctxt.frame_len, frame_len);
+    let outer_scopes = mem::take(&mut ctxt.scopes);
+
+    let (body_ty, body) = compile_node(ctxt, body)?;
+
+    if body_ty != output {
+        return Err(CompileError::TypeMismatch {
+            expected: output,
+            found: body_ty,
+        });
+    }
+
+    ctxt.fn_table.borrow_mut()[id] = Some(Rc::new(CompiledFn {
+        thunk: body,
+        frame_len: ctxt.frame_len,
+        depth,
+    }));
+
+    ctxt.stack = outer_stack;
+    ctxt.vars = outer_vars;
+    ctxt.frame_len = outer_frame_len;
+    ctxt.scopes = outer_scopes;
+
+    let ty = Type::Unit;
+
+    let thunk = Box::new(|_: &mut RuntimeContext| Value::Unit);
+
+    Ok((ty, thunk))
+}
+
+fn compile_call(
+    ctxt: &mut CompilationContext,
+    name: &'static str,
+    args: Vec<Node>,
+) -> Result<(Type, Thunk), CompileError> {
+    let decl = ctxt
+        .fns
+        .get(name)
+        .ok_or_else(|| CompileError::UndefinedFn {
+            name,
+            suggestion: suggest(name, ctxt.fns.keys().copied()),
+        })?;
+
+    let id = decl.id;
+    let params = decl.params.clone();
+    let output = decl.output;
+
+    if args.len() != params.len() {
+        return Err(CompileError::ArgCountMismatch {
+            name,
+            expected: params.len(),
+            found: args.len(),
+        });
+    }
+
+    let args = args
+        .into_iter()
+        .zip(params)
+        .map(|(arg, param_ty)| {
+            let (arg_ty, arg) = compile_node(ctxt, arg)?;
+
+            if arg_ty != param_ty {
+                return Err(CompileError::TypeMismatch {
+                    expected: param_ty,
+                    found: arg_ty,
+                });
+            }
+
+            Ok(arg)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let fns = Rc::downgrade(&ctxt.fn_table);
+    let log = ctxt.log.clone();
+    let ty = output;
+
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+        // The table is owned by the running program and gets filled
+        // in before the program is returned, so neither can fail
+        let func = fns.upgrade().unwrap().borrow()[id].clone().unwrap();
+
+        let depth = ctxt.depth + func.depth;
+
+        if depth > MAX_CALL_DEPTH {
+            panic!("call stack exhausted: {} levels deep", ctxt.depth);
+        }
+
+        let mut frame = RuntimeContext {
+            stack: vec![Value::Unit; func.frame_len],
+            depth,
+        };
+
+        for (slot, arg) in args.iter().enumerate() {
+            frame.stack[slot] = arg(ctxt);
+        }
+
+        match &log {
+            Some(log) => trace::record_call(log, name, || (func.thunk)(&mut frame)),
+            None => (func.thunk)(&mut frame),
+        }
+    });
+
+    Ok((ty, thunk))
+}
+
+fn compile_load_state(
+    ctxt: &mut CompilationContext,
+    key: &'static str,
+    default: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let store = ctxt
+        .state_store
+        .clone()
+        .ok_or(CompileError::MissingStateStore)?;
+
+    let (ty, default) = compile_scoped(ctxt, default)?;
+
+    // Values of other types are most likely left behind by other programs
+    // (or older versions of this one), so they're treated as if there was
+    // no value at all
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| match store.load(key) {
+        Some(value) if value.ty() == ty => value,
+        _ => default(ctxt),
+    });
+
+    Ok((ty, thunk))
+}
+
+fn compile_save_state(
+    ctxt: &mut CompilationContext,
+    key: &'static str,
+    value: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let store = ctxt
+        .state_store
+        .clone()
+        .ok_or(CompileError::MissingStateStore)?;
+
+    let (_, value) = compile_node(ctxt, value)?;
+
+    let ty = Type::Unit;
+
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+        store.save(key, value(ctxt));
+        Value::Unit
+    });
+
+    Ok((ty, thunk))
+}
+
+fn compile_block(
+    ctxt: &mut CompilationContext,
+    nodes: Vec<Node>,
+) -> Result<(Type, Thunk), CompileError> {
+    if nodes.is_empty() {
+        return Err(CompileError::EmptyBlock);
+    }
+
+    let slots = ctxt.enter_scope();
+
+    let (tys, nodes): (Vec<_>, Vec<_>) = nodes
+        .into_iter()
+        .map(|node| compile_node(ctxt, node))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+
+    ctxt.exit_scope(slots);
+
+    let ty = tys.into_iter().last().unwrap();
+
+    let thunk = Box::new(move |ctxt: &mut RuntimeContext| {
+        let mut value = Value::Unit;
+
+        for node in &nodes {
+            value = node(ctxt);
+        }
+
+        value
+    });
+
+    Ok((ty, thunk))
+}
+
+/// Compiles comparison `op`; ordering comparisons support ints and floats,
+/// equality ones booleans as well.
+fn compile_cmp(
+    ctxt: &mut CompilationContext,
+    op: NodeKind,
+    lhs: Node,
+    rhs: Node,
+) -> Result<(Type, Thunk), CompileError> {
+    let (lhs_ty, lhs) = compile_node(ctxt, lhs)?;
+    let (rhs_ty, rhs) = compile_node(ctxt, rhs)?;
+
+    let thunk = match (op, lhs_ty, rhs_ty) {
+        (NodeKind::Gt, Type::Int, Type::Int) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_int, |lhs, rhs| lhs > rhs)
+        }
+        (NodeKind::Gt, Type::Float, Type::Float) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_float, |lhs, rhs| lhs > rhs)
+        }
+        (NodeKind::Lt, Type::Int, Type::Int) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_int, |lhs, rhs| lhs < rhs)
+        }
+        (NodeKind::Lt, Type::Float, Type::Float) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_float, |lhs, rhs| lhs < rhs)
+        }
+        (NodeKind::Ge, Type::Int, Type::Int) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_int, |lhs, rhs| lhs >= rhs)
+        }
+        (NodeKind::Ge, Type::Float, Type::Float) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_float, |lhs, rhs| lhs >= rhs)
+        }
+        (NodeKind::Le, Type::Int, Type::Int) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_int, |lhs, rhs| lhs <= rhs)
+        }
+        (NodeKind::Le, Type::Float, Type::Float) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_float, |lhs, rhs| lhs <= rhs)
+        }
+        (NodeKind::Eq, Type::Bool, Type::Bool) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_bool, |lhs, rhs| lhs == rhs)
+        }
+        (NodeKind::Eq, Type::Int, Type::Int) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_int, |lhs, rhs| lhs == rhs)
+        }
+        (NodeKind::Eq, Type::Float, Type::Float) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_float, |lhs, rhs| lhs == rhs)
+        }
+        (NodeKind::Ne, Type::Bool, Type::Bool) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_bool, |lhs, rhs| lhs != rhs)
+        }
+        (NodeKind::Ne, Type::Int, Type::Int) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_int, |lhs, rhs| lhs != rhs)
+        }
+        (NodeKind::Ne, Type::Float, Type::Float) => {
+            cmp_thunk(lhs, rhs, Value::unwrap_float, |lhs, rhs| lhs != rhs)
+        }
+
+        (op, lhs, rhs) => {
+            return Err(CompileError::UnsupportedOp { op, lhs, rhs });
+        }
+    };
+
+    Ok((Type::Bool, thunk))
+}
+
+fn cmp_thunk<T>(
+    lhs: Thunk,
+    rhs: Thunk,
+    unwrap: impl Fn(Value) -> T + 'static,
+    cmp: impl Fn(T, T) -> bool + 'static,
+) -> Thunk {
+    Box::new(move |ctxt: &mut RuntimeContext| {
+        let lhs = unwrap(lhs(ctxt));
+        let rhs = unwrap(rhs(ctxt));
+
+        Value::Bool(cmp(lhs, rhs))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::thread;
+
+    /// Runs `f` on a thread with the default stack size of spawned threads,
+    /// which is what [`MAX_DEPTH`] and [`MAX_CALL_DEPTH`] are picked for.
+    fn on_small_stack<T>(f: impl FnOnce() -> T + Send + 'static) -> thread::Result<T>
+    where
+        T: Send + 'static,
+    {
+        thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(f)
+            .unwrap()
+            .join()
+    }
+
+    fn leak(src: String) -> &'static str {
+        Box::leak(src.into_boxed_str())
+    }
+
+    #[test]
+    fn max_depth_fits_small_stack() {
+        let output = on_small_stack(|| {
+            let n = MAX_DEPTH - 2;
+            let src = leak(format!("{}input{}", "{".repeat(n), "}".repeat(n)));
+            let prog = parse(src).unwrap();
+
+            assert_eq!(MAX_DEPTH, prog.body.depth());
+
+            let f = TypedProgram::<i32, i32>::new(prog.clone())
+                .and_then(compile)
+                .unwrap();
+            let g = TypedProgram::<i32, i32>::new(prog)
+                .and_then(compile_traced)
+                .unwrap();
+
+            (f(5), g(5).0)
+        });
+
+        assert_eq!((5, 5), output.unwrap());
+    }
+
+    #[test]
+    fn max_depth_rejects_deeper_programs() {
+        let n = MAX_DEPTH - 1;
+        let src = leak(format!("{}input{}", "{".repeat(n), "}".repeat(n)));
+
+        assert!(parse(src).is_err());
+
+        let mut body = Node::Var("input");
+
+        for _ in 0..MAX_DEPTH {
+            body = Node::Block(vec![body]);
+        }
+
+        let prog = Program {
+            input: Type::Int,
+            output: None,
+            body,
+        };
+
+        assert!(matches!(
+            TypedProgram::<i32, i32>::new(prog).and_then(compile),
+            Err(CompileError::TooDeep { .. }),
+        ));
+    }
+
+    fn compile_src(src: &'static str, opts: CompileOptions) -> Result<(), CompileError> {
+        TypedProgram::<i32, i32>::new(parse(src).unwrap())
+            .and_then(|prog| compile_with(prog, &opts))
+            .map(drop)
+    }
+
+    #[test]
+    fn size_limits() {
+        let src = "let x = 1; let y = 2; x + y";
+
+        assert_eq!(
+            Ok(()),
+            compile_src(
+                src,
+                CompileOptions {
+                    max_nodes: Some(8),
+                    max_vars: Some(2),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        assert_eq!(
+            Err(CompileError::TooManyNodes { nodes: 8, max: 7 }),
+            compile_src(
+                src,
+                CompileOptions {
+                    max_nodes: Some(7),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        assert_eq!(
+            Err(CompileError::TooManyVars { vars: 2, max: 1 }),
+            compile_src(
+                src,
+                CompileOptions {
+                    max_vars: Some(1),
+                    ..Default::default()
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn nesting_limits() {
+        let src = "let i = 0; while i < 2 { let j = 0; while j < 2 { j = j + 1 }; i = i + 1 }; i";
+
+        assert_eq!(
+            Ok(()),
+            compile_src(
+                src,
+                CompileOptions {
+                    max_loop_nesting: Some(2),
+                    max_expression_depth: Some(8),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        assert_eq!(
+            Err(CompileError::LoopsNestedTooDeeply { nesting: 2, max: 1 }),
+            compile_src(
+                src,
+                CompileOptions {
+                    max_loop_nesting: Some(1),
+                    ..Default::default()
+                },
+            ),
+        );
+
+        assert_eq!(
+            Err(CompileError::TooDeep { depth: 8, max: 7 }),
+            compile_src(
+                src,
+                CompileOptions {
+                    max_expression_depth: Some(7),
+                    ..Default::default()
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn compile_report() {
+        let prog = parse("let x = 1 + 2; x + input").unwrap();
+        let typed = || TypedProgram::<i32, i32>::new(prog.clone()).unwrap();
+
+        let started_at = Instant::now();
+        let (f, report) = compile_with_report(typed(), &CompileOptions::default()).unwrap();
+        let elapsed = started_at.elapsed();
+
+        assert_eq!(8, report.nodes_before);
+        assert_eq!(6, report.nodes_after);
+        assert!(report.validate_time + report.optimize_time + report.codegen_time <= elapsed);
+        assert_eq!(13, f(10));
+
+        let opts = CompileOptions {
+            disable_optimizations: true,
+            ..Default::default()
+        };
+
+        let (_, report) = compile_with_report(typed(), &opts).unwrap();
+
+        assert_eq!(8, report.nodes_before);
+        assert_eq!(8, report.nodes_after);
+    }
+
+    #[test]
+    fn signature_mismatch() {
+        let mut prog = parse("let x = input; { x > 0 }").unwrap();
+
+        prog.output = Some(Type::Int);
+
+        assert!(matches!(
+            TypedProgram::<i32, i32>::new(prog).and_then(compile),
+            Err(CompileError::SignatureMismatch {
+                declared: Type::Int,
+                inferred: Type::Bool,
+                path,
+            }) if path == vec![1, 0].into(),
+        ));
+    }
+
+    #[test]
+    fn rust_type_mismatch() {
+        let prog = parse("input").unwrap();
+
+        assert_eq!(
+            CompileError::RustTypeMismatch {
+                declared: Type::Int,
+                rust: Type::Float,
+            },
+            TypedProgram::<f64, f64>::new(prog).unwrap_err(),
+        );
+    }
+
+    fn run(prog: Program, input: i32) -> Result<i32, CompileError> {
+        let f = TypedProgram::<i32, i32>::new(prog).and_then(compile)?;
+
+        Ok(f(input))
+    }
+
+    fn run_src(src: &'static str, input: i32) -> Result<i32, CompileError> {
+        run(parse(src).unwrap(), input)
+    }
+
+    fn int(value: i32) -> Box<Node> {
+        Box::new(Node::Const(Value::Int(value)))
+    }
+
+    #[test]
+    fn scoping() {
+        assert_eq!(Ok(3), run_src("let x = 1; { let x = 2; x } + x", 0));
+        assert_eq!(Ok(2), run_src("let x = 1; let x = x + 1; x", 0));
+        assert_eq!(
+            Ok(5),
+            run_src("let x = 5; if x > 0 { let x = 1; x } else { 0 }; x", 0)
+        );
+
+        assert!(matches!(
+            run_src("{ let y = 1; y }; y", 0),
+            Err(CompileError::UndefinedVar { name: "y", .. }),
+        ));
+    }
+
+    #[test]
+    fn branch_lets_dont_leak() {
+        // if false let y = 1; y
+        let prog = Program {
+            input: Type::Int,
+            output: None,
+            body: Node::Block(vec![
+                Node::If {
+                    cond: Box::new(Node::Const(Value::Bool(false))),
+                    then: Box::new(Node::Let {
+                        name: "y",
+                        value: int(1),
+                    }),
+                    else_: None,
+                },
+                Node::Var("y"),
+            ]),
+        };
+
+        assert!(matches!(
+            run(prog, 0),
+            Err(CompileError::UndefinedVar { name: "y", .. }),
+        ));
+    }
+
+    #[test]
+    fn function_lets_dont_leak() {
+        assert_eq!(
+            Ok(6),
+            run_src(
+                "let x = 1; fn f(n: int) -> int { let x = n; x }; f(5) + x",
+                0
+            )
+        );
+
+        // let y = 1; { fn f() -> unit let y = 2; 0 }; y
+        let prog = Program {
+            input: Type::Int,
+            output: None,
+            body: Node::Block(vec![
+                Node::Let {
+                    name: "y",
+                    value: int(1),
+                },
+                Node::Block(vec![
+                    Node::Fn {
+                        name: "f",
+                        params: vec![],
+                        output: Type::Unit,
+                        body: Box::new(Node::Let {
+                            name: "y",
+                            value: int(2),
+                        }),
+                    },
+                    Node::Const(Value::Int(0)),
+                ]),
+                Node::Var("y"),
+            ]),
+        };
+
+        assert_eq!(Ok(1), run(prog, 0));
+    }
+
+    #[test]
+    fn folding_keeps_type_errors() {
+        let prog = parse("if true { 1 } else { false }").unwrap();
+
+        assert_eq!(None, prog.body.eval_const());
+
+        assert_eq!(
+            Err(CompileError::TypeMismatch {
+                expected: Type::Int,
+                found: Type::Bool,
+            }),
+            run(prog, 0),
+        );
+    }
+
+    /// `fn f(n: int) -> int`, which recurses `n` times, each call nested
+    /// within `nesting` additions
+    fn nested_recursion(nesting: usize) -> Program {
+        let src = format!(
+            "fn f(n: int) -> int {{ if n > 0 {{ {}f(n - 1){} }} else {{ 0 }} }}; f(input)",
+            "1 + (".repeat(nesting),
+            ")".repeat(nesting),
+        );
+
+        parse(leak(src)).unwrap()
+    }
+
+    #[test]
+    fn recursion() {
+        assert_eq!(Ok(0), run(nested_recursion(3), 0));
+        assert_eq!(Ok(30), run(nested_recursion(3), 10));
+
+        let fib =
+            "fn fib(n: int) -> int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }; fib(input)";
+
+        assert_eq!(Ok(55), run_src(fib, 10));
+    }
+
+    #[test]
+    fn deep_recursion_panics_instead_of_overflowing() {
+        for traced in [false, true] {
+            let result = on_small_stack(move || {
+                let prog = TypedProgram::<i32, i32>::new(nested_recursion(50)).unwrap();
+
+                if traced {
+                    compile_traced(prog).unwrap()(i32::MAX).0
+                } else {
+                    compile(prog).unwrap()(i32::MAX)
+                }
+            });
+
+            let message = *result.unwrap_err().downcast::<String>().unwrap();
+
+            assert!(message.starts_with("call stack exhausted"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let prog = nested_recursion(10);
+
+        let body_depth = match &prog.body {
+            Node::Block(stmts) => match &stmts[0] {
+                Node::Fn { body, .. } => body.depth(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        // f(input) calls itself `input` times
+        let max = (MAX_CALL_DEPTH / body_depth) as i32 - 1;
+
+        let result = on_small_stack(move || {
+            let f = TypedProgram::<i32, i32>::new(prog)
+                .and_then(compile)
+                .unwrap();
+
+            assert_eq!(max * 10, f(max));
+
+            panic::catch_unwind(AssertUnwindSafe(|| f(max + 1))).is_err()
+        });
+
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(
+            Err(CompileError::UndefinedVar {
+                name: "cont",
+                suggestion: Some("count"),
+            }),
+            run_src("let count = 1; cont", 0),
+        );
+
+        assert_eq!(
+            Err(CompileError::UndefinedFn {
+                name: "doubel",
+                suggestion: Some("double"),
+            }),
+            run_src("fn double(n: int) -> int { n + n }; doubel(input)", 0),
+        );
+
+        assert_eq!(
+            Err(CompileError::UndefinedFn {
+                name: "triple",
+                suggestion: None,
+            }),
+            run_src("fn double(n: int) -> int { n + n }; triple(input)", 0),
+        );
+    }
+}
@@ -0,0 +1,567 @@
+use crate::{Node, Program, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// A variable used in a loop's condition is assigned more than once per
+    /// iteration
+    LoopCounterModifiedTwice,
+
+    /// A loop's or `if`'s condition always evaluates to the same value
+    ConstantCondition,
+
+    /// `let` of a name that's already in scope
+    ShadowedVariable,
+
+    /// An integer literal other than -1, 0, 1 or 2 that isn't bound to a
+    /// variable of its own
+    MagicConstant,
+
+    /// Lint reported by a [`LintRule`] of given name
+    Custom(&'static str),
+}
+
+/// Custom lint, run alongside the built-in ones when registered through
+/// [`LintConfig::with_rule()`]
+pub trait LintRule {
+    /// Name of the rule; its lints are reported as [`LintKind::Custom`] with
+    /// this name, which can be used to configure their severity.
+    fn name(&self) -> &'static str;
+
+    /// Checks the program, returning a message for each problem found.
+    fn check(&self, prog: &Program) -> Vec<String>;
+}
+
+impl fmt::Debug for dyn LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LintRule({})", self.name())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warning"),
+            Self::Deny => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LintConfig {
+    severities: HashMap<LintKind, Severity>,
+    rules: Vec<Rc<dyn LintRule>>,
+}
+
+impl LintConfig {
+    pub fn with(mut self, kind: LintKind, severity: Severity) -> Self {
+        self.severities.insert(kind, severity);
+        self
+    }
+
+    pub fn with_rule(mut self, rule: impl LintRule + 'static) -> Self {
+        self.rules.push(Rc::new(rule));
+        self
+    }
+
+    pub fn severity(&self, kind: LintKind) -> Severity {
+        self.severities
+            .get(&kind)
+            .copied()
+            .unwrap_or(Severity::Warn)
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            severities: FromIterator::from_iter(vec![(LintKind::MagicConstant, Severity::Allow)]),
+            rules: Vec::new(),
+        }
+    }
+}
+
+pub fn lint(prog: &Program) -> Vec<Lint> {
+    lint_with(prog, &LintConfig::default())
+}
+
+pub fn lint_with(prog: &Program, config: &LintConfig) -> Vec<Lint> {
+    let mut linter = Linter {
+        config,
+        vars: FromIterator::from_iter(vec!["input"]),
+        lints: Default::default(),
+    };
+
+    linter.visit(&prog.body, false);
+
+    for rule in &config.rules {
+        for message in rule.check(prog) {
+            linter.report(LintKind::Custom(rule.name()), message);
+        }
+    }
+
+    linter.lints
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    vars: HashSet<&'static str>,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, kind: LintKind, message: String) {
+        let severity = self.config.severity(kind);
+
+        if severity != Severity::Allow {
+            self.lints.push(Lint {
+                kind,
+                severity,
+                message,
+            });
+        }
+    }
+
+    /// `bound` is true when `node` is the value of a `let`, in which case a
+    /// constant is already named and isn't considered magic.
+    fn visit(&mut self, node: &Node, bound: bool) {
+        match node {
+            Node::Let { name, value } => {
+                self.visit(value, true);
+
+                if !self.vars.insert(name) {
+                    self.report(
+                        LintKind::ShadowedVariable,
+                        format!("variable `{}` shadows an earlier declaration", name),
+                    );
+                }
+            }
+
+            Node::Assign { value, .. } | Node::SaveState { value, .. } => {
+                self.visit(value, false);
+            }
+
+            Node::LoadState { default, .. } => {
+                self.visit_branch(default);
+            }
+
+            Node::Const(Value::Int(value)) => {
+                if !bound && !matches!(value, -1..=2) {
+                    self.report(
+                        LintKind::MagicConstant,
+                        format!(
+                            "magic constant `{}`; consider binding it to a variable",
+                            value
+                        ),
+                    );
+                }
+            }
+
+            Node::Const(_) | Node::Var(_) | Node::Hole(..) => {}
+
+            Node::Gt { lhs, rhs }
+            | Node::Lt { lhs, rhs }
+            | Node::Ge { lhs, rhs }
+            | Node::Le { lhs, rhs }
+            | Node::Eq { lhs, rhs }
+            | Node::Ne { lhs, rhs }
+            | Node::Add { lhs, rhs }
+            | Node::Sub { lhs, rhs } => {
+                self.visit(lhs, false);
+                self.visit(rhs, false);
+            }
+
+            Node::While { cond, body } => {
+                if let Some(Value::Bool(value)) = cond.eval_const() {
+                    self.report(
+                        LintKind::ConstantCondition,
+                        format!("loop condition is always {}", value),
+                    );
+                }
+
+                let mut reads = BTreeSet::new();
+                let mut assigns = BTreeMap::new();
+
+                collect_reads(cond, &mut reads);
+                count_assigns(body, &mut HashSet::new(), &mut assigns);
+
+                for name in reads {
+                    if assigns.get(name).copied().unwrap_or(0) > 1 {
+                        self.report(
+                            LintKind::LoopCounterModifiedTwice,
+                            format!(
+                                "loop counter `{}` is modified more than once per iteration",
+                                name
+                            ),
+                        );
+                    }
+                }
+
+                self.visit(cond, false);
+                self.visit_branch(body);
+            }
+
+            Node::If { cond, then, else_ } => {
+                if let Some(Value::Bool(value)) = cond.eval_const() {
+                    self.report(
+                        LintKind::ConstantCondition,
+                        format!("`if` condition is always {}", value),
+                    );
+                }
+
+                self.visit(cond, false);
+                self.visit_branch(then);
+
+                if let Some(else_) = else_ {
+                    self.visit_branch(else_);
+                }
+            }
+
+            // Functions run in frames of their own, where only their
+            // parameters are declared
+            Node::Fn { params, body, .. } => {
+                let params = params.iter().map(|(name, _)| *name).collect();
+                let vars = mem::replace(&mut self.vars, params);
+
+                self.visit(body, false);
+                self.vars = vars;
+            }
+
+            Node::Call { args, .. } => {
+                for arg in args {
+                    self.visit(arg, false);
+                }
+            }
+
+            // Variables declared within the block go out of scope once it
+            // ends
+            Node::Block(nodes) => {
+                let vars = self.vars.clone();
+
+                for node in nodes {
+                    self.visit(node, false);
+                }
+
+                self.vars = vars;
+            }
+        }
+    }
+
+    /// Visits a branch (see [`Node::is_branch()`]), whose variables go out of
+    /// scope once it ends.
+    fn visit_branch(&mut self, node: &Node) {
+        let vars = self.vars.clone();
+
+        self.visit(node, false);
+        self.vars = vars;
+    }
+}
+
+fn collect_reads(node: &Node, reads: &mut BTreeSet<&'static str>) {
+    match node {
+        Node::Var(name) => {
+            reads.insert(name);
+        }
+
+        Node::Let { value, .. }
+        | Node::Assign { value, .. }
+        | Node::LoadState { default: value, .. }
+        | Node::SaveState { value, .. } => {
+            collect_reads(value, reads);
+        }
+
+        // Function's body reads variables of its own frame
+        Node::Const(_) | Node::Fn { .. } | Node::Hole(..) => {}
+
+        Node::Gt { lhs, rhs }
+        | Node::Lt { lhs, rhs }
+        | Node::Ge { lhs, rhs }
+        | Node::Le { lhs, rhs }
+        | Node::Eq { lhs, rhs }
+        | Node::Ne { lhs, rhs }
+        | Node::Add { lhs, rhs }
+        | Node::Sub { lhs, rhs }
+        | Node::While {
+            cond: lhs,
+            body: rhs,
+        } => {
+            collect_reads(lhs, reads);
+            collect_reads(rhs, reads);
+        }
+
+        Node::If { cond, then, else_ } => {
+            collect_reads(cond, reads);
+            collect_reads(then, reads);
+
+            if let Some(else_) = else_ {
+                collect_reads(else_, reads);
+            }
+        }
+
+        Node::Call { args: nodes, .. } | Node::Block(nodes) => {
+            for node in nodes {
+                collect_reads(node, reads);
+            }
+        }
+    }
+}
+
+/// Counts assignments of variables declared outside of `node`; `shadowed`
+/// contains variables redeclared within `node` so far, whose assignments
+/// refer to the inner declarations instead.
+fn count_assigns(
+    node: &Node,
+    shadowed: &mut HashSet<&'static str>,
+    assigns: &mut BTreeMap<&'static str, usize>,
+) {
+    match node {
+        Node::Assign { name, value } => {
+            if !shadowed.contains(name) {
+                *assigns.entry(name).or_default() += 1;
+            }
+
+            count_assigns(value, shadowed, assigns);
+        }
+
+        Node::Let { name, value } => {
+            count_assigns(value, shadowed, assigns);
+            shadowed.insert(name);
+        }
+
+        Node::SaveState { value, .. } => {
+            count_assigns(value, shadowed, assigns);
+        }
+
+        Node::LoadState { default, .. } => {
+            count_branch_assigns(default, shadowed, assigns);
+        }
+
+        // Function's body assigns variables of its own frame
+        Node::Const(_) | Node::Var(_) | Node::Fn { .. } | Node::Hole(..) => {}
+
+        Node::Gt { lhs, rhs }
+        | Node::Lt { lhs, rhs }
+        | Node::Ge { lhs, rhs }
+        | Node::Le { lhs, rhs }
+        | Node::Eq { lhs, rhs }
+        | Node::Ne { lhs, rhs }
+        | Node::Add { lhs, rhs }
+        | Node::Sub { lhs, rhs } => {
+            count_assigns(lhs, shadowed, assigns);
+            count_assigns(rhs, shadowed, assigns);
+        }
+
+        Node::While { cond, body } => {
+            count_assigns(cond, shadowed, assigns);
+            count_branch_assigns(body, shadowed, assigns);
+        }
+
+        Node::If { cond, then, else_ } => {
+            count_assigns(cond, shadowed, assigns);
+
+            // Only one of the branches runs, so each variable is assigned as
+            // many times as the branch that assigns it more often does
+            let mut branch_assigns = BTreeMap::new();
+            let mut else_assigns = BTreeMap::new();
+
+            count_branch_assigns(then, shadowed, &mut branch_assigns);
+
+            if let Some(else_) = else_ {
+                count_branch_assigns(else_, shadowed, &mut else_assigns);
+            }
+
+            for (name, count) in else_assigns {
+                let branch_count = branch_assigns.entry(name).or_default();
+
+                *branch_count = (*branch_count).max(count);
+            }
+
+            for (name, count) in branch_assigns {
+                *assigns.entry(name).or_default() += count;
+            }
+        }
+
+        Node::Call { args, .. } => {
+            for arg in args {
+                count_assigns(arg, shadowed, assigns);
+            }
+        }
+
+        // Variables declared within the block go out of scope once it ends
+        Node::Block(nodes) => {
+            let outer = shadowed.clone();
+
+            for node in nodes {
+                count_assigns(node, shadowed, assigns);
+            }
+
+            *shadowed = outer;
+        }
+    }
+}
+
+/// Counts assignments within a branch (see [`Node::is_branch()`]), whose
+/// variables go out of scope once it ends.
+fn count_branch_assigns(
+    node: &Node,
+    shadowed: &mut HashSet<&'static str>,
+    assigns: &mut BTreeMap<&'static str, usize>,
+) {
+    let outer = shadowed.clone();
+
+    count_assigns(node, shadowed, assigns);
+    *shadowed = outer;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn kinds(src: &'static str, config: &LintConfig) -> Vec<LintKind> {
+        lint_with(&parse(src).unwrap(), config)
+            .into_iter()
+            .map(|lint| lint.kind)
+            .collect()
+    }
+
+    #[test]
+    fn loop_counter_modified_twice() {
+        let config = LintConfig::default().with(LintKind::ShadowedVariable, Severity::Allow);
+
+        assert_eq!(
+            vec![LintKind::LoopCounterModifiedTwice],
+            kinds(
+                "let i = 0; while i < 8 { i = i + 1; i = i + 1 }; i",
+                &config
+            ),
+        );
+
+        // Only one of the branches runs
+        assert_eq!(
+            Vec::<LintKind>::new(),
+            kinds(
+                "let i = 0; while i < 8 { if i > 2 { i = i + 1 } else { i = i + 2 } }; i",
+                &config,
+            ),
+        );
+
+        // Assignments of shadowing variables don't count
+        assert_eq!(
+            Vec::<LintKind>::new(),
+            kinds(
+                "let i = 0; while i < 8 { i = i + 1; { let i = 0; i = i + 1 } }; i",
+                &config,
+            ),
+        );
+
+        // ... but only once they've been declared
+        assert_eq!(
+            vec![LintKind::LoopCounterModifiedTwice],
+            kinds(
+                "let i = 0; while i < 8 { i = i + 1; { i = i + 1; let i = 0; i } }; i",
+                &config,
+            ),
+        );
+    }
+
+    #[test]
+    fn constant_condition() {
+        assert_eq!(
+            vec![LintKind::ConstantCondition, LintKind::ConstantCondition],
+            kinds(
+                "while false { 0 }; if 1 > 0 { 1 } else { 2 }",
+                &LintConfig::default(),
+            ),
+        );
+    }
+
+    #[test]
+    fn shadowed_variable() {
+        let config = LintConfig::default();
+
+        assert_eq!(
+            vec![LintKind::ShadowedVariable],
+            kinds("let x = 1; let x = 2; x", &config),
+        );
+
+        assert_eq!(
+            Vec::<LintKind>::new(),
+            kinds("let x = 1; { let y = 2; y }; let y = x; y", &config),
+        );
+    }
+
+    #[test]
+    fn magic_constant() {
+        let src = "let x = 42; x + 100 - 2";
+
+        assert_eq!(Vec::<LintKind>::new(), kinds(src, &LintConfig::default()));
+
+        let lints = lint_with(
+            &parse(src).unwrap(),
+            &LintConfig::default().with(LintKind::MagicConstant, Severity::Deny),
+        );
+
+        assert_eq!(1, lints.len());
+        assert_eq!(LintKind::MagicConstant, lints[0].kind);
+        assert_eq!(Severity::Deny, lints[0].severity);
+
+        assert_eq!(
+            "error: magic constant `100`; consider binding it to a variable",
+            lints[0].to_string(),
+        );
+    }
+
+    struct NoLoops;
+
+    impl LintRule for NoLoops {
+        fn name(&self) -> &'static str {
+            "no_loops"
+        }
+
+        fn check(&self, prog: &Program) -> Vec<String> {
+            (0..prog.info().loops)
+                .map(|_| "loops aren't allowed".to_string())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn custom_rule() {
+        let src = "let i = 0; while i < 2 { i = i + 1 }; i";
+        let config = LintConfig::default().with_rule(NoLoops);
+        let lints = lint_with(&parse(src).unwrap(), &config);
+
+        assert_eq!(1, lints.len());
+        assert_eq!(LintKind::Custom("no_loops"), lints[0].kind);
+        assert_eq!("warning: loops aren't allowed", lints[0].to_string());
+
+        let config = config.with(LintKind::Custom("no_loops"), Severity::Allow);
+
+        assert_eq!(Vec::<LintKind>::new(), kinds(src, &config));
+    }
+}
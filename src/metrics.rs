@@ -0,0 +1,94 @@
+use crate::CompileReport;
+use std::fmt;
+use std::time::Duration;
+
+/// Receives events about compiled programs, e.g. to forward them into the
+/// host's monitoring system.
+///
+/// All methods do nothing by default, so sinks only need to implement the
+/// events they care about.
+pub trait MetricsSink {
+    /// Called after a program has been successfully compiled.
+    fn on_compile(&self, report: &CompileReport) {
+        let _ = report;
+    }
+
+    /// Called after each run of a compiled program.
+    fn on_run(&self, duration: Duration) {
+        let _ = duration;
+    }
+}
+
+impl fmt::Debug for dyn MetricsSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MetricsSink")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile_with, parse, CompileOptions, TypedProgram};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Recorder {
+        compiles: RefCell<Vec<usize>>,
+        runs: RefCell<usize>,
+    }
+
+    impl MetricsSink for Recorder {
+        fn on_compile(&self, report: &CompileReport) {
+            self.compiles.borrow_mut().push(report.nodes_before);
+        }
+
+        fn on_run(&self, _: Duration) {
+            *self.runs.borrow_mut() += 1;
+        }
+    }
+
+    struct Noop;
+
+    impl MetricsSink for Noop {}
+
+    fn compile(src: &'static str, metrics: Rc<dyn MetricsSink>) -> impl Fn(i32) -> i32 {
+        let opts = CompileOptions {
+            metrics: Some(metrics),
+            ..Default::default()
+        };
+
+        TypedProgram::<i32, i32>::new(parse(src).unwrap())
+            .and_then(|prog| compile_with(prog, &opts))
+            .unwrap()
+    }
+
+    #[test]
+    fn callbacks() {
+        let recorder = Rc::new(Recorder::default());
+        let f = compile("input + 1", recorder.clone());
+
+        assert_eq!(vec![4], *recorder.compiles.borrow());
+        assert_eq!(0, *recorder.runs.borrow());
+
+        assert_eq!(2, f(1));
+        assert_eq!(3, f(2));
+        assert_eq!(2, *recorder.runs.borrow());
+
+        let opts = CompileOptions {
+            metrics: Some(recorder.clone()),
+            ..Default::default()
+        };
+
+        assert!(TypedProgram::<i32, i32>::new(parse("x").unwrap())
+            .and_then(|prog| compile_with(prog, &opts))
+            .is_err());
+
+        assert_eq!(vec![4], *recorder.compiles.borrow());
+    }
+
+    #[test]
+    fn default_callbacks() {
+        assert_eq!(2, compile("input + 1", Rc::new(Noop))(1));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::equivalence::compare;
+use crate::{
+    compile_program, CompileError, CompileOptions, Counterexample, EquivalenceStrategy, Node,
+    Program, Value,
+};
+
+/// Mutant of a program whose behavior changed after optimizations
+#[derive(Clone, Debug)]
+pub struct OptimizationMismatch {
+    pub prog: Program,
+    pub counterexample: Counterexample,
+}
+
+/// Returns variations of the program with a single node mutated: arithmetic
+/// operators swapped, comparison operands swapped (or, for equality, the
+/// comparison negated), branches of `if`s swapped, numeric constants off by
+/// one and boolean constants negated.
+///
+/// Every mutation preserves types, so mutants compile whenever the original
+/// program does.
+pub fn mutants(prog: &Program) -> Vec<Program> {
+    let mut mutants = Vec::new();
+
+    for idx in 0..prog.body.size() {
+        for variant in 0.. {
+            let mut mutant = prog.clone();
+            let mut remaining = idx;
+
+            match mutate(&mut mutant.body, &mut remaining, variant) {
+                Some(true) => mutants.push(mutant),
+                Some(false) => break,
+                None => unreachable!(),
+            }
+        }
+    }
+
+    mutants
+}
+
+/// Checks that optimizations don't change the behavior of the program or any
+/// of its [`mutants()`], by running each of them compiled both with and
+/// without optimizations.
+///
+/// Note that mutants can loop for much longer than the original program (or
+/// forever), so the inputs chosen by `strategy` should be kept small.
+///
+/// Fails if the program doesn't compile.
+pub fn check_optimizations(
+    prog: &Program,
+    strategy: &EquivalenceStrategy,
+) -> Result<Option<OptimizationMismatch>, CompileError> {
+    let optimized = CompileOptions::default();
+
+    let unoptimized = CompileOptions {
+        disable_optimizations: true,
+        ..Default::default()
+    };
+
+    for prog in Some(prog.clone()).into_iter().chain(mutants(prog)) {
+        let lhs = compile_program(prog.clone(), None, &unoptimized)?;
+        let rhs = compile_program(prog.clone(), None, &optimized)?;
+
+        if let Some(counterexample) = compare(prog.input, &lhs, &rhs, strategy) {
+            return Ok(Some(OptimizationMismatch {
+                prog,
+                counterexample,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Applies `variant`-th mutation to the `idx`-th node (in pre-order) of the
+/// tree.
+///
+/// Returns `Some(true)` if the node got mutated, `Some(false)` if the node
+/// has no such variant, and `None` if the node hasn't been found in this
+/// subtree (in which case `idx` gets decremented by the subtree's size).
+fn mutate(node: &mut Node, idx: &mut usize, variant: usize) -> Option<bool> {
+    if *idx > 0 {
+        *idx -= 1;
+
+        return node
+            .children_mut()
+            .into_iter()
+            .find_map(|child| mutate(child, idx, variant));
+    }
+
+    let mutated = match (&*node, variant) {
+        (Node::Add { lhs, rhs }, 0) => Node::Sub {
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        },
+
+        (Node::Sub { lhs, rhs }, 0) => Node::Add {
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        },
+
+        (Node::Gt { lhs, rhs }, 0) => Node::Gt {
+            lhs: rhs.clone(),
+            rhs: lhs.clone(),
+        },
+
+        (Node::Lt { lhs, rhs }, 0) => Node::Lt {
+            lhs: rhs.clone(),
+            rhs: lhs.clone(),
+        },
+
+        (Node::Ge { lhs, rhs }, 0) => Node::Ge {
+            lhs: rhs.clone(),
+            rhs: lhs.clone(),
+        },
+
+        (Node::Le { lhs, rhs }, 0) => Node::Le {
+            lhs: rhs.clone(),
+            rhs: lhs.clone(),
+        },
+
+        (Node::Eq { lhs, rhs }, 0) => Node::Ne {
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        },
+
+        (Node::Ne { lhs, rhs }, 0) => Node::Eq {
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        },
+
+        (
+            Node::If {
+                cond,
+                then,
+                else_: Some(else_),
+            },
+            0,
+        ) => Node::If {
+            cond: cond.clone(),
+            then: else_.clone(),
+            else_: Some(then.clone()),
+        },
+
+        (Node::Const(Value::Int(value)), 0) => Node::Const(Value::Int(value.wrapping_add(1))),
+        (Node::Const(Value::Int(value)), 1) => Node::Const(Value::Int(value.wrapping_sub(1))),
+        (Node::Const(Value::Float(value)), 0) => Node::Const(Value::Float(value + 1.0)),
+        (Node::Const(Value::Float(value)), 1) => Node::Const(Value::Float(value - 1.0)),
+        (Node::Const(Value::Bool(value)), 0) => Node::Const(Value::Bool(!*value)),
+
+        _ => {
+            return Some(false);
+        }
+    };
+
+    *node = mutated;
+
+    Some(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{examples_corpus, parse};
+
+    #[test]
+    fn mutants() {
+        let mutants: Vec<_> = super::mutants(&parse("if input >= 1 { 2.5 } else { 0.0 }").unwrap())
+            .into_iter()
+            .map(|mutant| mutant.body)
+            .collect();
+
+        let expected = [
+            "if input >= 1 { 0.0 } else { 2.5 }",
+            "if 1 >= input { 2.5 } else { 0.0 }",
+            "if input >= 2 { 2.5 } else { 0.0 }",
+            "if input >= 0 { 2.5 } else { 0.0 }",
+            "if input >= 1 { 3.5 } else { 0.0 }",
+            "if input >= 1 { 1.5 } else { 0.0 }",
+            "if input >= 1 { 2.5 } else { 1.0 }",
+            "if input >= 1 { 2.5 } else { -1.0 }",
+        ];
+
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|src| parse(src).unwrap().body)
+            .collect();
+
+        assert_eq!(expected, mutants);
+    }
+
+    #[test]
+    fn check_optimizations() {
+        let strategy = EquivalenceStrategy::Exhaustive { range: -3..=3 };
+
+        assert!(matches!(
+            super::check_optimizations(&examples_corpus::abs(), &strategy),
+            Ok(None),
+        ));
+
+        assert!(matches!(
+            super::check_optimizations(&parse("input + x").unwrap(), &strategy),
+            Err(CompileError::UndefinedVar { name: "x", .. }),
+        ));
+    }
+}
@@ -0,0 +1,927 @@
+use crate::{Node, Program, Type, Value, MAX_DEPTH};
+use std::error::Error;
+use std::fmt;
+
+/// Problem found by [`parse()`], along with its (1-based) position in the
+/// source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses a program from its textual form, e.g.:
+///
+/// ```text
+/// let x = 0;
+/// let n = input;
+///
+/// while n > 0 {
+///     x = x + 2;
+///     n = n - 1
+/// }
+///
+/// x
+/// ```
+///
+/// Statements are separated with `;` (optional after `while`, `if`, `fn` and
+/// blocks), and - like blocks - the program evaluates to its last statement
+/// (or to `()`, if it's empty). Functions are declared as
+/// `fn name(param: type, ...) -> type { ... }`, where types are `int`,
+/// `float`, `bool` and `unit`; `if`s and `while`s don't take parentheses
+/// around their conditions. State is accessed through
+/// `load_state("key", default)` and `save_state("key", value)`, with keys
+/// written as string literals (without escape sequences). Floats are written
+/// with a fractional part (`1.0`, not `1`), or as `inf` and `nan`.
+///
+/// The source has to be `'static`, because variables reuse their names from
+/// it. The parsed program takes an `int` and has its output type inferred -
+/// both can be adjusted through [`Program`]'s fields.
+pub fn parse(src: &'static str) -> Result<Program, ParseError> {
+    let tokens = lex(src)?;
+
+    let mut parser = Parser {
+        src,
+        tokens,
+        pos: 0,
+
+        // The program's body is a block, which counts as a level as well
+        depth: 1,
+    };
+    let body = parser.stmts()?;
+
+    parser.expect(Token::Eof)?;
+
+    Ok(Program {
+        input: Type::Int,
+        output: None,
+        body,
+    })
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Token {
+    Int(i64),
+    Float(f64),
+    Ident(&'static str),
+    Str(&'static str),
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Semicolon,
+    Comma,
+    Colon,
+    Arrow,
+    Assign,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    Ne,
+    Plus,
+    Minus,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "`{}`", value),
+            Self::Float(value) => write!(f, "`{:?}`", value),
+            Self::Ident(name) => write!(f, "`{}`", name),
+            Self::Str(value) => write!(f, "`\"{}\"`", value),
+            Self::LBrace => write!(f, "`{{`"),
+            Self::RBrace => write!(f, "`}}`"),
+            Self::LParen => write!(f, "`(`"),
+            Self::RParen => write!(f, "`)`"),
+            Self::Semicolon => write!(f, "`;`"),
+            Self::Comma => write!(f, "`,`"),
+            Self::Colon => write!(f, "`:`"),
+            Self::Arrow => write!(f, "`->`"),
+            Self::Assign => write!(f, "`=`"),
+            Self::Gt => write!(f, "`>`"),
+            Self::Lt => write!(f, "`<`"),
+            Self::Ge => write!(f, "`>=`"),
+            Self::Le => write!(f, "`<=`"),
+            Self::Eq => write!(f, "`==`"),
+            Self::Ne => write!(f, "`!=`"),
+            Self::Plus => write!(f, "`+`"),
+            Self::Minus => write!(f, "`-`"),
+            Self::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Token along with its position (byte offset) in the source
+#[derive(Copy, Clone, Debug)]
+struct Spanned {
+    token: Token,
+    offset: usize,
+}
+
+fn error_at(src: &str, offset: usize, message: String) -> ParseError {
+    let before = &src[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    ParseError {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        message,
+    }
+}
+
+fn lex(src: &'static str) -> Result<Vec<Spanned>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+
+    while let Some((idx, ch)) = chars.next() {
+        let token = match ch {
+            ch if ch.is_whitespace() => {
+                continue;
+            }
+
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                while chars.next_if(|(_, ch)| *ch != '\n').is_some() {}
+                continue;
+            }
+
+            '0'..='9' => {
+                let mut end = idx + 1;
+
+                while let Some((idx, _)) = chars.next_if(|(_, ch)| ch.is_ascii_digit()) {
+                    end = idx + 1;
+                }
+
+                let is_float = src[end..].starts_with('.')
+                    && src[end + 1..].starts_with(|ch: char| ch.is_ascii_digit());
+
+                if is_float {
+                    chars.next();
+
+                    while let Some((idx, _)) = chars.next_if(|(_, ch)| ch.is_ascii_digit()) {
+                        end = idx + 1;
+                    }
+
+                    // Digits always make up a valid float (at worst, an
+                    // infinite one)
+                    Token::Float(src[idx..end].parse().unwrap())
+                } else {
+                    match src[idx..end].parse() {
+                        Ok(value) => Token::Int(value),
+
+                        Err(_) => {
+                            return Err(error_at(
+                                src,
+                                idx,
+                                format!("integer literal too large: {}", &src[idx..end]),
+                            ));
+                        }
+                    }
+                }
+            }
+
+            ch if ch.is_alphabetic() || ch == '_' => {
+                let mut end = idx + ch.len_utf8();
+
+                while let Some((idx, ch)) =
+                    chars.next_if(|(_, ch)| ch.is_alphanumeric() || *ch == '_')
+                {
+                    end = idx + ch.len_utf8();
+                }
+
+                match &src[idx..end] {
+                    "inf" => Token::Float(f64::INFINITY),
+                    "nan" => Token::Float(f64::NAN),
+                    ident => Token::Ident(ident),
+                }
+            }
+
+            '"' => {
+                let len = src[idx + 1..]
+                    .find('"')
+                    .ok_or_else(|| error_at(src, idx, "unterminated string literal".into()))?;
+
+                while chars.next_if(|(_, ch)| *ch != '"').is_some() {}
+                chars.next();
+
+                Token::Str(&src[idx + 1..idx + 1 + len])
+            }
+
+            '-' if matches!(chars.peek(), Some((_, '>'))) => {
+                chars.next();
+                Token::Arrow
+            }
+
+            '>' | '<' | '=' | '!' if matches!(chars.peek(), Some((_, '='))) => {
+                chars.next();
+
+                match ch {
+                    '>' => Token::Ge,
+                    '<' => Token::Le,
+                    '=' => Token::Eq,
+                    _ => Token::Ne,
+                }
+            }
+
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '=' => Token::Assign,
+            '>' => Token::Gt,
+            '<' => Token::Lt,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+
+            ch => {
+                return Err(error_at(
+                    src,
+                    idx,
+                    format!("unexpected character: `{}`", ch),
+                ));
+            }
+        };
+
+        tokens.push(Spanned { token, offset: idx });
+    }
+
+    tokens.push(Spanned {
+        token: Token::Eof,
+        offset: src.len(),
+    });
+
+    Ok(tokens)
+}
+
+const KEYWORDS: &[&str] = &[
+    "else",
+    "false",
+    "fn",
+    "if",
+    "let",
+    "load_state",
+    "save_state",
+    "true",
+    "while",
+];
+
+struct Parser {
+    src: &'static str,
+    tokens: Vec<Spanned>,
+    pos: usize,
+
+    /// Nesting depth of the node being parsed; like compilation, parsing
+    /// recurses on the Rust stack, so it's limited to [`MAX_DEPTH`]
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Token {
+        self.tokens[self.pos].token
+    }
+
+    fn peek_nth(&self, n: usize) -> Token {
+        self.tokens
+            .get(self.pos + n)
+            .map_or(Token::Eof, |spanned| spanned.token)
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek();
+
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+
+        token
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        if self.peek() == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &'static str) -> bool {
+        self.eat(Token::Ident(keyword))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}, found {}", token, self.peek())))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<&'static str, ParseError> {
+        match self.peek() {
+            Token::Ident(name) if !KEYWORDS.contains(&name) => {
+                self.next();
+                Ok(name)
+            }
+
+            token => Err(self.error(format!("expected identifier, found {}", token))),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+
+        if self.depth > MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Checks the depth of an operator's node after it's been parsed.
+    ///
+    /// Operands are parsed before it's known how many operators follow them,
+    /// i.e. how deep they'll end up being nested, so they're only counted as
+    /// the node's children - the actual depth is checked here instead.
+    fn check_depth(&self, node: &Node) -> Result<(), ParseError> {
+        if self.depth + node.depth() > MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+
+        Ok(())
+    }
+
+    fn too_deep(&self) -> ParseError {
+        self.error(format!("program too deep (max {} levels)", MAX_DEPTH))
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        error_at(self.src, self.tokens[self.pos].offset, message)
+    }
+
+    /// stmt (";" stmt)* ";"?
+    ///
+    /// Parses statements up to (but excluding) the closing `}` or the end of
+    /// input; like in Rust, a block without any statements evaluates to `()`.
+    fn stmts(&mut self) -> Result<Node, ParseError> {
+        let mut stmts = Vec::new();
+
+        while !matches!(self.peek(), Token::RBrace | Token::Eof) {
+            let (stmt, needs_semicolon) = self.stmt()?;
+
+            stmts.push(stmt);
+
+            if !self.eat(Token::Semicolon)
+                && needs_semicolon
+                && !matches!(self.peek(), Token::RBrace | Token::Eof)
+            {
+                return Err(self.error(format!("expected `;`, found {}", self.peek())));
+            }
+        }
+
+        if stmts.is_empty() {
+            stmts.push(Node::Const(Value::Unit));
+        }
+
+        Ok(Node::Block(stmts))
+    }
+
+    /// Returns the statement and whether it has to be followed by a `;` (or
+    /// the end of its block).
+    fn stmt(&mut self) -> Result<(Node, bool), ParseError> {
+        if self.eat_keyword("let") {
+            self.enter()?;
+
+            let name = self.expect_ident()?;
+
+            self.expect(Token::Assign)?;
+
+            let value = Box::new(self.expr()?);
+
+            self.leave();
+
+            return Ok((Node::Let { name, value }, true));
+        }
+
+        if self.eat_keyword("fn") {
+            return Ok((self.fn_decl()?, false));
+        }
+
+        if let (Token::Ident(name), Token::Assign) = (self.peek(), self.peek_nth(1)) {
+            if !KEYWORDS.contains(&name) {
+                self.next();
+                self.next();
+                self.enter()?;
+
+                let value = Box::new(self.expr()?);
+
+                self.leave();
+
+                return Ok((Node::Assign { name, value }, true));
+            }
+        }
+
+        let needs_semicolon = !matches!(self.peek(), Token::LBrace | Token::Ident("while" | "if"));
+
+        Ok((self.expr()?, needs_semicolon))
+    }
+
+    /// name "(" (param ":" type ("," param ":" type)*)? ")" "->" type block
+    fn fn_decl(&mut self) -> Result<Node, ParseError> {
+        self.enter()?;
+
+        let name = self.expect_ident()?;
+        let mut params = Vec::new();
+
+        self.expect(Token::LParen)?;
+
+        while !self.eat(Token::RParen) {
+            if !params.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+
+            let param = self.expect_ident()?;
+
+            self.expect(Token::Colon)?;
+            params.push((param, self.ty()?));
+        }
+
+        self.expect(Token::Arrow)?;
+
+        let output = self.ty()?;
+        let body = Box::new(self.block()?);
+
+        self.leave();
+
+        Ok(Node::Fn {
+            name,
+            params,
+            output,
+            body,
+        })
+    }
+
+    fn ty(&mut self) -> Result<Type, ParseError> {
+        for ty in [Type::Unit, Type::Bool, Type::Int, Type::Float] {
+            if self.eat_keyword(ty.name()) {
+                return Ok(ty);
+            }
+        }
+
+        Err(self.error(format!("expected type, found {}", self.peek())))
+    }
+
+    /// "{" stmts "}"
+    fn block(&mut self) -> Result<Node, ParseError> {
+        self.expect(Token::LBrace)?;
+        self.enter()?;
+
+        let block = self.stmts()?;
+
+        self.leave();
+        self.expect(Token::RBrace)?;
+
+        Ok(block)
+    }
+
+    /// sum ((">" | "<" | ">=" | "<=" | "==" | "!=") sum)?
+    fn expr(&mut self) -> Result<Node, ParseError> {
+        let lhs = self.sum()?;
+
+        let cmp: fn(Box<Node>, Box<Node>) -> Node = match self.peek() {
+            Token::Gt => |lhs, rhs| Node::Gt { lhs, rhs },
+            Token::Lt => |lhs, rhs| Node::Lt { lhs, rhs },
+            Token::Ge => |lhs, rhs| Node::Ge { lhs, rhs },
+            Token::Le => |lhs, rhs| Node::Le { lhs, rhs },
+            Token::Eq => |lhs, rhs| Node::Eq { lhs, rhs },
+            Token::Ne => |lhs, rhs| Node::Ne { lhs, rhs },
+            _ => return Ok(lhs),
+        };
+
+        self.next();
+
+        let rhs = self.sum()?;
+        let node = cmp(Box::new(lhs), Box::new(rhs));
+
+        self.check_depth(&node)?;
+
+        Ok(node)
+    }
+
+    /// atom (("+" | "-") atom)*
+    fn sum(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.atom()?;
+
+        if !matches!(self.peek(), Token::Plus | Token::Minus) {
+            return Ok(lhs);
+        }
+
+        loop {
+            if self.eat(Token::Plus) {
+                lhs = Node::Add {
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(self.atom()?),
+                };
+            } else if self.eat(Token::Minus) {
+                lhs = Node::Sub {
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(self.atom()?),
+                };
+            } else {
+                break;
+            }
+        }
+
+        self.check_depth(&lhs)?;
+
+        Ok(lhs)
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        // Blocks count their level on their own
+        if self.peek() == Token::LBrace {
+            return self.block();
+        }
+
+        // Parenthesized expressions count as a level as well - they don't
+        // produce a node, but parsing them recurses all the same
+        self.enter()?;
+
+        let atom = self.atom_inner();
+
+        self.leave();
+        atom
+    }
+
+    fn atom_inner(&mut self) -> Result<Node, ParseError> {
+        match self.peek() {
+            Token::Int(_) | Token::Float(_) | Token::Minus => {
+                let negative = self.eat(Token::Minus);
+
+                let value = match self.peek() {
+                    Token::Int(value) if negative => -value,
+                    Token::Int(value) => value,
+
+                    Token::Float(value) => {
+                        self.next();
+
+                        let value = if negative { -value } else { value };
+
+                        return Ok(Node::Const(Value::Float(value)));
+                    }
+
+                    token => return Err(self.error(format!("expected number, found {}", token))),
+                };
+
+                let value = i32::try_from(value)
+                    .map_err(|_| self.error(format!("integer literal out of range: {}", value)))?;
+
+                self.next();
+
+                Ok(Node::Const(Value::Int(value)))
+            }
+
+            Token::LParen => {
+                self.next();
+
+                if self.eat(Token::RParen) {
+                    return Ok(Node::Const(Value::Unit));
+                }
+
+                let expr = self.expr()?;
+
+                self.expect(Token::RParen)?;
+
+                Ok(expr)
+            }
+
+            Token::Ident("true") => {
+                self.next();
+
+                Ok(Node::Const(Value::Bool(true)))
+            }
+
+            Token::Ident("false") => {
+                self.next();
+
+                Ok(Node::Const(Value::Bool(false)))
+            }
+
+            Token::Ident("while") => {
+                self.next();
+
+                let cond = Box::new(self.expr()?);
+                let body = Box::new(self.block()?);
+
+                Ok(Node::While { cond, body })
+            }
+
+            Token::Ident("if") => {
+                self.next();
+                self.if_rest()
+            }
+
+            Token::Ident(name @ ("load_state" | "save_state")) => {
+                self.next();
+                self.state(name)
+            }
+
+            Token::Ident(_) => {
+                let name = self.expect_ident()?;
+
+                if !self.eat(Token::LParen) {
+                    return Ok(Node::Var(name));
+                }
+
+                let mut args = Vec::new();
+
+                while !self.eat(Token::RParen) {
+                    if !args.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+
+                    args.push(self.expr()?);
+                }
+
+                Ok(Node::Call { name, args })
+            }
+
+            token => Err(self.error(format!("expected expression, found {}", token))),
+        }
+    }
+
+    /// "(" string "," expr ")", following `load_state` or `save_state`
+    fn state(&mut self, name: &'static str) -> Result<Node, ParseError> {
+        self.expect(Token::LParen)?;
+
+        let key = match self.peek() {
+            Token::Str(key) => key,
+            token => return Err(self.error(format!("expected string, found {}", token))),
+        };
+
+        self.next();
+        self.expect(Token::Comma)?;
+
+        let value = Box::new(self.expr()?);
+
+        self.expect(Token::RParen)?;
+
+        Ok(if name == "load_state" {
+            Node::LoadState {
+                key,
+                default: value,
+            }
+        } else {
+            Node::SaveState { key, value }
+        })
+    }
+
+    /// cond block ("else" (block | "if" if_rest))?
+    fn if_rest(&mut self) -> Result<Node, ParseError> {
+        let cond = Box::new(self.expr()?);
+        let then = Box::new(self.block()?);
+
+        let else_ = if self.eat_keyword("else") {
+            if self.eat_keyword("if") {
+                self.enter()?;
+
+                let if_ = self.if_rest()?;
+
+                self.leave();
+
+                Some(Box::new(if_))
+            } else {
+                Some(Box::new(self.block()?))
+            }
+        } else {
+            None
+        };
+
+        Ok(Node::If { cond, then, else_ })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, compile_with, CompileOptions, MemoryStateStore, TypedProgram};
+    use std::rc::Rc;
+
+    fn run(src: &'static str, input: i32) -> i32 {
+        let f = TypedProgram::<i32, i32>::new(parse(src).unwrap())
+            .and_then(compile)
+            .unwrap();
+
+        f(input)
+    }
+
+    #[test]
+    fn associativity() {
+        let int = |value| Box::new(Node::Const(Value::Int(value)));
+
+        assert_eq!(
+            Node::Block(vec![Node::Add {
+                lhs: Box::new(Node::Sub {
+                    lhs: int(1),
+                    rhs: int(2),
+                }),
+                rhs: int(3),
+            }]),
+            parse("1 - 2 + 3").unwrap().body,
+        );
+
+        assert_eq!(2, run("1 - 2 + 3", 0));
+        assert_eq!(1, run("if 1 - 2 + 3 > 1 { 1 } else { 0 }", 0));
+    }
+
+    #[test]
+    fn else_if() {
+        let src = "if input > 0 { 1 } else if input < 0 { -1 } else { 0 }";
+
+        assert_eq!(1, run(src, 5));
+        assert_eq!(-1, run(src, -5));
+        assert_eq!(0, run(src, 0));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            ParseError {
+                line: 1,
+                column: 9,
+                message: "expected expression, found `;`".into(),
+            },
+            parse("let x = ;").unwrap_err(),
+        );
+
+        assert_eq!(
+            ParseError {
+                line: 2,
+                column: 4,
+                message: "expected expression, found end of input".into(),
+            },
+            parse("let x = 1;\nx +").unwrap_err(),
+        );
+    }
+
+    #[test]
+    fn empty_blocks() {
+        assert_eq!(
+            0,
+            run(
+                "let n = input; while n > 0 { n = n - 1; if n > 5 { } }; n",
+                10
+            )
+        );
+
+        assert_eq!(
+            Node::Block(vec![Node::Const(Value::Unit)]),
+            parse("").unwrap().body,
+        );
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let values = [
+            Value::Unit,
+            Value::Bool(true),
+            Value::Int(0),
+            Value::Int(i32::MIN),
+            Value::Int(i32::MAX),
+            Value::Float(0.0),
+            Value::Float(-0.0),
+            Value::Float(1.0),
+            Value::Float(-0.1),
+            Value::Float(1e20),
+            Value::Float(1e-300),
+            Value::Float(f64::MIN),
+            Value::Float(f64::MAX),
+            Value::Float(f64::MIN_POSITIVE),
+            Value::Float(f64::INFINITY),
+            Value::Float(f64::NEG_INFINITY),
+            Value::Float(f64::NAN),
+        ];
+
+        for value in values {
+            let src = Box::leak(value.to_string().into_boxed_str());
+
+            assert_eq!(
+                Node::Block(vec![Node::Const(value.clone())]),
+                parse(src).unwrap().body,
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn state() {
+        let prog = parse(
+            r#"
+            let runs = load_state("runs", 0) + 1;
+            save_state("runs", runs);
+            runs
+            "#,
+        )
+        .unwrap();
+
+        let opts = CompileOptions {
+            state_store: Some(Rc::new(MemoryStateStore::new())),
+            ..Default::default()
+        };
+
+        let f = compile_with(TypedProgram::<i32, i32>::new(prog).unwrap(), &opts).unwrap();
+
+        assert_eq!(1, f(0));
+        assert_eq!(2, f(0));
+    }
+
+    #[test]
+    fn state_errors() {
+        assert_eq!(
+            ParseError {
+                line: 1,
+                column: 12,
+                message: "unterminated string literal".into(),
+            },
+            parse("load_state(\"runs, 0)").unwrap_err(),
+        );
+
+        assert_eq!(
+            ParseError {
+                line: 1,
+                column: 12,
+                message: "expected string, found `runs`".into(),
+            },
+            parse("load_state(runs, 0)").unwrap_err(),
+        );
+
+        assert_eq!(
+            ParseError {
+                line: 1,
+                column: 5,
+                message: "expected identifier, found `save_state`".into(),
+            },
+            parse("let save_state = 1").unwrap_err(),
+        );
+    }
+
+    #[test]
+    fn depth() {
+        // Each template nests `X` this many levels deeper
+        let templates = [
+            ("while false { X }", 2),
+            ("if true { X } else { 0 }", 2),
+            ("if false { 0 } else if true { X }", 3),
+            ("{ let x = X; x }", 2),
+            ("{ input = X; input }", 2),
+            ("{ fn f() -> int { X }; f() }", 3),
+            ("X + 1", 1),
+            ("1 - { X }", 2),
+            ("{ X } > 0", 2),
+            ("f(X)", 1),
+            ("load_state(\"k\", X)", 1),
+        ];
+
+        for (template, levels) in templates {
+            let mut src = String::from("input");
+            let mut depth = 0;
+
+            loop {
+                let next = template.replace('X', &src);
+
+                match parse(Box::leak(next.clone().into_boxed_str())) {
+                    Ok(prog) => depth = prog.body.depth(),
+                    Err(_) => break,
+                }
+
+                src = next;
+            }
+
+            assert!(
+                depth <= MAX_DEPTH && depth + levels > MAX_DEPTH,
+                "{}: {}",
+                template,
+                depth,
+            );
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{Node, Program};
+use std::fmt;
+
+/// Address of a node within a program, as a sequence of child indices (see
+/// [`Node::children()`]) leading from the program's body to the node
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodePath(Vec<usize>);
+
+impl NodePath {
+    /// Returns path of the program's body.
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn child(&self, idx: usize) -> Self {
+        let mut path = self.clone();
+
+        path.push(idx);
+        path
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.0.split_last()?;
+
+        Some(Self(parent.to_vec()))
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.0
+    }
+
+    pub(crate) fn push(&mut self, idx: usize) {
+        self.0.push(idx);
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.0.pop();
+    }
+}
+
+impl From<Vec<usize>> for NodePath {
+    fn from(indices: Vec<usize>) -> Self {
+        Self(indices)
+    }
+}
+
+impl fmt::Display for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "body")?;
+
+        for idx in &self.0 {
+            write!(f, ".{}", idx)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Program {
+    pub fn get(&self, path: &NodePath) -> Option<&Node> {
+        path.0.iter().try_fold(&self.body, |node, idx| {
+            node.children().into_iter().nth(*idx)
+        })
+    }
+
+    pub fn get_mut(&mut self, path: &NodePath) -> Option<&mut Node> {
+        path.0.iter().try_fold(&mut self.body, |node, idx| {
+            node.children_mut().into_iter().nth(*idx)
+        })
+    }
+
+    /// Replaces node at given path, returning the previous one; returns
+    /// `None` (leaving the program untouched) if there's no such node.
+    pub fn replace(&mut self, path: &NodePath, node: Node) -> Option<Node> {
+        let target = self.get_mut(path)?;
+
+        Some(std::mem::replace(target, node))
+    }
+
+    /// Returns path of the node whose value the program evaluates to, i.e.
+    /// of its body's trailing statement (of the trailing statement and so
+    /// on, for nested blocks).
+    pub(crate) fn output_path(&self) -> NodePath {
+        let mut path = NodePath::root();
+        let mut node = &self.body;
+
+        while let Node::Block(stmts) = node {
+            match stmts.last() {
+                Some(stmt) => {
+                    path.push(stmts.len() - 1);
+                    node = stmt;
+                }
+                None => break,
+            }
+        }
+
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Value};
+
+    #[test]
+    fn paths() {
+        let path = NodePath::root().child(1).child(0);
+
+        assert_eq!(&[1, 0], path.indices());
+        assert_eq!("body.1.0", path.to_string());
+        assert_eq!(Some(NodePath::from(vec![1])), path.parent());
+        assert_eq!(Some(NodePath::root()), path.parent().unwrap().parent());
+        assert_eq!(None, NodePath::root().parent());
+    }
+
+    #[test]
+    fn get_and_replace() {
+        let mut prog = parse("let x = 1; x + 2").unwrap();
+
+        assert_eq!(Some(&prog.body), prog.get(&NodePath::root()));
+        assert_eq!(Some(&Node::Var("x")), prog.get(&vec![1, 0].into()));
+        assert_eq!(None, prog.get(&vec![1, 2].into()));
+        assert_eq!(None, prog.get(&vec![1, 0, 0].into()));
+
+        assert_eq!(
+            Some(Node::Const(Value::Int(2))),
+            prog.replace(&vec![1, 1].into(), Node::Var("input")),
+        );
+
+        assert_eq!(parse("let x = 1; x + input").unwrap().body, prog.body);
+        assert_eq!(None, prog.replace(&vec![2].into(), Node::Var("input")));
+        assert_eq!(parse("let x = 1; x + input").unwrap().body, prog.body);
+    }
+}
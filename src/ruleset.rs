@@ -0,0 +1,141 @@
+use crate::{
+    compile_program, CompileError, CompileOptions, IntoValue, RuntimeContext, Thunk, Type,
+    TypedProgram, Value,
+};
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// Set of boolean programs (rules) evaluated together against a single input
+pub struct CompiledRuleset<Input> {
+    rules: Vec<Thunk>,
+    stack_len: usize,
+    spare_stack: Cell<Option<Vec<Value>>>,
+    _input: PhantomData<fn(Input)>,
+}
+
+impl<Input> CompiledRuleset<Input>
+where
+    Input: IntoValue,
+{
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Evaluates every rule against `input`, returning their results in the
+    /// same order the rules were given to [`compile_ruleset()`].
+    pub fn eval(&self, input: Input) -> Vec<bool> {
+        let input = input.into_value();
+
+        let mut ctxt = RuntimeContext {
+            stack: self
+                .spare_stack
+                .take()
+                .unwrap_or_else(|| vec![Value::Unit; self.stack_len]),
+            depth: 0,
+        };
+
+        let results = self
+            .rules
+            .iter()
+            .map(|rule| {
+                ctxt.stack.fill(Value::Unit);
+                ctxt.stack[0] = input.clone();
+
+                rule(&mut ctxt).unwrap_bool()
+            })
+            .collect();
+
+        self.spare_stack.set(Some(ctxt.stack));
+
+        results
+    }
+
+    /// Returns indices of rules that match `input`.
+    pub fn matching(&self, input: Input) -> Vec<usize> {
+        self.eval(input)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, matches)| if matches { Some(idx) } else { None })
+            .collect()
+    }
+}
+
+/// Compiles many small predicates at once, sharing a single runtime stack
+/// between them.
+pub fn compile_ruleset<Input>(
+    rules: Vec<TypedProgram<Input, bool>>,
+) -> Result<CompiledRuleset<Input>, CompileError>
+where
+    Input: IntoValue,
+{
+    let opts = CompileOptions::default();
+    let mut stack_len = 1;
+
+    let rules = rules
+        .into_iter()
+        .map(|rule| {
+            let rule = compile_program(rule.into_inner(), Some(Type::Bool), &opts)?;
+
+            stack_len = stack_len.max(rule.stack_len);
+            Ok(rule.thunk)
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(CompiledRuleset {
+        rules,
+        stack_len,
+        spare_stack: Cell::new(None),
+        _input: PhantomData,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn rules(srcs: &[&'static str]) -> Vec<TypedProgram<i32, bool>> {
+        srcs.iter()
+            .map(|src| TypedProgram::new(parse(src).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn eval() {
+        let ruleset = compile_ruleset(rules(&[
+            "input > 0",
+            "input == 5",
+            "let x = input; let y = 10; x < y",
+            "let x = 0; x == 0",
+        ]))
+        .unwrap();
+
+        assert_eq!(4, ruleset.len());
+        assert!(!ruleset.is_empty());
+
+        assert_eq!(vec![true, true, true, true], ruleset.eval(5));
+        assert_eq!(vec![true, false, false, true], ruleset.eval(20));
+        assert_eq!(vec![2, 3], ruleset.matching(-1));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            compile_ruleset(rules(&["input > 0", "x > 0"])),
+            Err(CompileError::UndefinedVar { name: "x", .. }),
+        ));
+
+        assert!(matches!(
+            compile_ruleset(rules(&["input + 1"])),
+            Err(CompileError::SignatureMismatch {
+                declared: Type::Bool,
+                inferred: Type::Int,
+                ..
+            }),
+        ));
+    }
+}
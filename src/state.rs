@@ -0,0 +1,75 @@
+use crate::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Key-value storage that programs persist data in between their runs,
+/// through [`Node::LoadState`](crate::Node::LoadState) and
+/// [`Node::SaveState`](crate::Node::SaveState).
+///
+/// The store itself is untyped - it's the programs that expect values of
+/// particular types under particular keys. Programs sharing a store should
+/// agree on the type stored under each key: `load_state` ignores values of
+/// types other than its default's (evaluating to the default instead), and
+/// `save_state` overwrites whatever has been stored before.
+pub trait StateStore {
+    /// Returns the value stored under given key, if any.
+    fn load(&self, key: &str) -> Option<Value>;
+
+    /// Stores the value under given key, replacing the previous one.
+    fn save(&self, key: &str, value: Value);
+}
+
+impl fmt::Debug for dyn StateStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StateStore")
+    }
+}
+
+/// [`StateStore`] that keeps the values in memory, so they live only as long
+/// as the store does
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    values: RefCell<HashMap<String, Value>>,
+}
+
+impl MemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn load(&self, key: &str) -> Option<Value> {
+        self.values.borrow().get(key).cloned()
+    }
+
+    fn save(&self, key: &str, value: Value) {
+        self.values.borrow_mut().insert(key.to_owned(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile_with, parse, CompileOptions, TypedProgram};
+    use std::rc::Rc;
+
+    #[test]
+    fn load_ignores_values_of_other_types() {
+        let store = Rc::new(MemoryStateStore::new());
+
+        store.save("runs", Value::Bool(true));
+
+        let opts = CompileOptions {
+            state_store: Some(store.clone()),
+            ..Default::default()
+        };
+
+        let prog = parse(r#"let runs = load_state("runs", 0) + 1; save_state("runs", runs); runs"#);
+        let f = compile_with(TypedProgram::<i32, i32>::new(prog.unwrap()).unwrap(), &opts).unwrap();
+
+        assert_eq!(1, f(0));
+        assert_eq!(Some(Value::Int(1)), store.load("runs"));
+    }
+}
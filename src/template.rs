@@ -0,0 +1,197 @@
+use crate::{
+    compile_node, CompilationContext, CompileError, MemoryStateStore, Node, NodePath, Program,
+    Type, Value, MAX_DEPTH,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+impl Program {
+    /// Returns ids and types of holes left in this program.
+    pub fn holes(&self) -> BTreeMap<usize, Type> {
+        let mut holes = BTreeMap::new();
+
+        collect_holes(&self.body, &mut holes);
+        holes
+    }
+
+    /// Creates a copy of this program with holes replaced by given subtrees.
+    ///
+    /// Holes without a corresponding entry in `fills` are left in place, so a
+    /// template can be instantiated in stages.
+    ///
+    /// Fails if any fill isn't of its hole's type
+    /// ([`CompileError::FillTypeMismatch`]) or if the instantiated program
+    /// doesn't type-check otherwise. Fills are type-checked in their new
+    /// surroundings, so they can refer to variables and functions declared
+    /// by the template and by other fills, but not by fills of later stages.
+    pub fn instantiate(&self, fills: &HashMap<usize, Node>) -> Result<Program, CompileError> {
+        let mut prog = self.clone();
+        let mut filled = HashMap::new();
+
+        fill_holes(&mut prog.body, fills, &mut NodePath::root(), &mut filled);
+        check_fills(prog.clone(), filled)?;
+
+        Ok(prog)
+    }
+}
+
+fn collect_holes(node: &Node, holes: &mut BTreeMap<usize, Type>) {
+    if let Node::Hole(id, ty) = node {
+        holes.insert(*id, *ty);
+    }
+
+    for child in node.children() {
+        collect_holes(child, holes);
+    }
+}
+
+/// Replaces holes with their fills, collecting ids and types of the filled
+/// holes by their paths into `filled`.
+fn fill_holes(
+    node: &mut Node,
+    fills: &HashMap<usize, Node>,
+    path: &mut NodePath,
+    filled: &mut HashMap<NodePath, (usize, Type)>,
+) {
+    if let Node::Hole(id, ty) = node {
+        if let Some(fill) = fills.get(id) {
+            filled.insert(path.clone(), (*id, *ty));
+            *node = fill.clone();
+        }
+
+        return;
+    }
+
+    for (idx, child) in node.children_mut().into_iter().enumerate() {
+        path.push(idx);
+        fill_holes(child, fills, path, filled);
+        path.pop();
+    }
+}
+
+/// Type-checks the instantiated program, making sure that filled holes (see
+/// [`fill_holes()`]) got subtrees of their types.
+fn check_fills(
+    mut prog: Program,
+    filled: HashMap<NodePath, (usize, Type)>,
+) -> Result<(), CompileError> {
+    if filled.is_empty() {
+        return Ok(());
+    }
+
+    let depth = prog.body.depth();
+
+    if depth > MAX_DEPTH {
+        return Err(CompileError::TooDeep {
+            depth,
+            max: MAX_DEPTH,
+        });
+    }
+
+    // Holes left for later stages don't compile, but any node of their type
+    // would type-check the same
+    stub_holes(&mut prog.body);
+
+    // The store never gets accessed, it's there only so that programs using
+    // state compile
+    let mut ctxt =
+        CompilationContext::new(prog.input, None, Some(Rc::new(MemoryStateStore::new())));
+
+    ctxt.fills = filled;
+
+    compile_node(&mut ctxt, prog.body).map(drop)
+}
+
+/// Replaces holes with constants of their types.
+fn stub_holes(node: &mut Node) {
+    if let Node::Hole(_, ty) = node {
+        *node = Node::Const(match ty {
+            Type::Unit => Value::Unit,
+            Type::Bool => Value::Bool(false),
+            Type::Int => Value::Int(0),
+            Type::Float => Value::Float(0.0),
+        });
+
+        return;
+    }
+
+    for child in node.children_mut() {
+        stub_holes(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, TypedProgram};
+
+    /// `input + ?0 + ?1`, with both holes of type int
+    fn template() -> Program {
+        let add = |lhs, rhs| Node::Add {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+
+        Program {
+            input: Type::Int,
+            output: None,
+            body: add(
+                add(Node::Var("input"), Node::Hole(0, Type::Int)),
+                Node::Hole(1, Type::Int),
+            ),
+        }
+    }
+
+    #[test]
+    fn fills_in_stages() {
+        let prog = template()
+            .instantiate(&HashMap::from([(0, Node::Var("input"))]))
+            .unwrap();
+
+        assert_eq!(1, prog.holes().len());
+
+        let prog = prog
+            .instantiate(&HashMap::from([(1, Node::Const(Value::Int(10)))]))
+            .unwrap();
+
+        let f = TypedProgram::<i32, i32>::new(prog)
+            .and_then(compile)
+            .unwrap();
+
+        assert_eq!(16, f(3));
+    }
+
+    #[test]
+    fn rejects_constant_of_another_type() {
+        let fills = HashMap::from([(0, Node::Const(Value::Bool(true)))]);
+
+        assert_eq!(
+            CompileError::FillTypeMismatch {
+                id: 0,
+                expected: Type::Int,
+                found: Type::Bool,
+            },
+            template().instantiate(&fills).unwrap_err(),
+        );
+    }
+
+    #[test]
+    fn rejects_subtree_of_another_type() {
+        let fills = HashMap::from([(
+            1,
+            Node::Gt {
+                lhs: Box::new(Node::Var("input")),
+                rhs: Box::new(Node::Const(Value::Int(0))),
+            },
+        )]);
+
+        assert_eq!(
+            CompileError::FillTypeMismatch {
+                id: 1,
+                expected: Type::Int,
+                found: Type::Bool,
+            },
+            template().instantiate(&fills).unwrap_err(),
+        );
+    }
+}
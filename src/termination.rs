@@ -0,0 +1,355 @@
+use crate::{Node, Program, Value};
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TerminationReport {
+    /// Loops in the order they appear in the program
+    pub loops: Vec<LoopTermination>,
+}
+
+impl TerminationReport {
+    pub fn all_terminate(&self) -> bool {
+        self.loops
+            .iter()
+            .all(|lp| lp.termination != Termination::Unknown)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoopTermination {
+    /// Variable that's been recognized as the loop's counter
+    pub counter: Option<&'static str>,
+    pub termination: Termination,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    /// The loop's counter moves strictly toward a bound and neither of them
+    /// depends on the input or the program's state, so the loop runs a fixed
+    /// number of times
+    Terminating,
+
+    /// The loop's counter moves strictly toward a bound, but the number of
+    /// iterations depends on the input (or the program's state or, within
+    /// functions, their arguments)
+    BoundedByInput,
+
+    /// The loop doesn't match any pattern known to terminate
+    Unknown,
+}
+
+impl Program {
+    /// Classifies every loop in the program by whether it's guaranteed to
+    /// terminate.
+    ///
+    /// This is a heuristic: a loop is recognized as terminating when its
+    /// condition compares `counter` and `bound` through `>`, `<`, `>=` or
+    /// `<=` (with the counter on either side), with `bound` free of side
+    /// effects and unchanged by the loop body, and the body's only change to
+    /// `counter` is a single top-level `counter = counter - k` (or `+ k`) with
+    /// `k > 0`.
+    pub fn termination_report(&self) -> TerminationReport {
+        let mut tainted = FromIterator::from_iter(vec!["input"]);
+        let mut report = TerminationReport::default();
+
+        // Functions can be called with any arguments, so their parameters are
+        // as unknown as the input is
+        taint_params(&self.body, &mut tainted);
+
+        while propagate_taint(&self.body, false, &mut tainted) {}
+
+        visit(&mut report, &tainted, &self.body);
+        report
+    }
+}
+
+fn visit(report: &mut TerminationReport, tainted: &HashSet<&'static str>, node: &Node) {
+    if let Node::While { cond, body } = node {
+        let counter = find_counter(cond, body).map(|counter| counter.name);
+
+        let termination = match counter {
+            Some(counter) => {
+                let mut reads = HashSet::new();
+
+                collect_reads(cond, &mut reads);
+
+                if reads.iter().any(|name| tainted.contains(name)) || tainted.contains(counter) {
+                    Termination::BoundedByInput
+                } else {
+                    Termination::Terminating
+                }
+            }
+
+            None => Termination::Unknown,
+        };
+
+        report.loops.push(LoopTermination {
+            counter,
+            termination,
+        });
+    }
+
+    for child in node.children() {
+        visit(report, tainted, child);
+    }
+}
+
+/// Loop counter recognized by [`find_counter()`]
+pub(crate) struct Counter<'a> {
+    pub name: &'static str,
+    pub bound: &'a Node,
+    pub step: Step,
+
+    /// Whether the loop also runs when the counter reaches the bound (i.e.
+    /// the condition is `>=` or `<=`)
+    pub inclusive: bool,
+}
+
+pub(crate) fn find_counter<'a>(cond: &'a Node, body: &Node) -> Option<Counter<'a>> {
+    // Loop runs while `greater > lesser` (or `>=`, when inclusive)
+    let (greater, lesser, inclusive) = match cond {
+        Node::Gt { lhs, rhs } => (lhs, rhs, false),
+        Node::Lt { lhs, rhs } => (rhs, lhs, false),
+        Node::Ge { lhs, rhs } => (lhs, rhs, true),
+        Node::Le { lhs, rhs } => (rhs, lhs, true),
+        _ => return None,
+    };
+
+    // Either side might be the counter - decreasing toward the other side
+    // when it's the greater one, increasing when it's the lesser one
+    [
+        (&**greater, &**lesser, false),
+        (&**lesser, &**greater, true),
+    ]
+    .into_iter()
+    .find_map(|(counter, bound, increasing)| match counter {
+        Node::Var(name) => recognize_counter(name, bound, increasing, inclusive, body),
+        _ => None,
+    })
+}
+
+fn recognize_counter<'a>(
+    name: &'static str,
+    bound: &'a Node,
+    increasing: bool,
+    inclusive: bool,
+    body: &Node,
+) -> Option<Counter<'a>> {
+    let mut bound_reads = HashSet::new();
+
+    collect_reads(bound, &mut bound_reads);
+
+    if bound_reads.contains(name) || bound_reads.iter().any(|var| assigns(body, var) > 0) {
+        return None;
+    }
+
+    // The bound is evaluated before every iteration, so its side effects
+    // (e.g. `while i < { m = m + 2; m }`) might move the bound or the counter
+    if assigns(bound, name) > 0 || bound_reads.iter().any(|var| assigns(bound, var) > 0) {
+        return None;
+    }
+
+    // The body might change the state, and with it the bound
+    if reads_state(bound) {
+        return None;
+    }
+
+    // If the body shadows the counter or the bound, its assignments might
+    // refer to the shadowing variables instead
+    if declares(body, name) || bound_reads.iter().any(|var| declares(body, var)) {
+        return None;
+    }
+
+    if assigns(body, name) != 1 {
+        return None;
+    }
+
+    let stmts = match body {
+        Node::Block(stmts) => stmts.iter().collect(),
+        body => vec![body],
+    };
+
+    let step = stmts.into_iter().find_map(|stmt| match stmt {
+        Node::Assign {
+            name: target,
+            value,
+        } if *target == name => Step::recognize(name, increasing, value),
+        _ => None,
+    })?;
+
+    Some(Counter {
+        name,
+        bound,
+        step,
+        inclusive,
+    })
+}
+
+#[derive(Copy, Clone)]
+pub(crate) enum Step {
+    /// counter = counter - k
+    Decreasing(i32),
+
+    /// counter = counter + k
+    Increasing(i32),
+}
+
+impl Step {
+    fn recognize(counter: &str, increasing: bool, value: &Node) -> Option<Self> {
+        let (lhs, rhs) = match (increasing, value) {
+            (false, Node::Sub { lhs, rhs }) | (true, Node::Add { lhs, rhs }) => (lhs, rhs),
+            _ => return None,
+        };
+
+        match (&**lhs, &**rhs) {
+            (Node::Var(name), Node::Const(Value::Int(k))) if *name == counter && *k > 0 => {
+                if increasing {
+                    Some(Self::Increasing(*k))
+                } else {
+                    Some(Self::Decreasing(*k))
+                }
+            }
+
+            _ => None,
+        }
+    }
+}
+
+fn taint_params(node: &Node, tainted: &mut HashSet<&'static str>) {
+    if let Node::Fn { params, .. } = node {
+        tainted.extend(params.iter().map(|(name, _)| *name));
+    }
+
+    for child in node.children() {
+        taint_params(child, tainted);
+    }
+}
+
+/// Marks every variable whose value might depend on a tainted variable (or
+/// on the program's state) as tainted itself; returns whether anything
+/// changed.
+///
+/// `implicit` tells whether the node runs under a tainted condition, in
+/// which case whatever it assigns depends on the taint, too.
+fn propagate_taint(node: &Node, implicit: bool, tainted: &mut HashSet<&'static str>) -> bool {
+    let mut changed = false;
+
+    if let Node::Let { name, value } | Node::Assign { name, value } = node {
+        if !tainted.contains(name) && (implicit || depends_on_taint(value, tainted)) {
+            tainted.insert(name);
+            changed = true;
+        }
+    }
+
+    // Whether (or how many times) branches run depends on their condition,
+    // e.g. `if input > 0 { n = 20 }`
+    let implicit = implicit
+        || match node {
+            Node::If { cond, .. } | Node::While { cond, .. } => depends_on_taint(cond, tainted),
+            _ => false,
+        };
+
+    for child in node.children() {
+        changed |= propagate_taint(child, implicit, tainted);
+    }
+
+    changed
+}
+
+fn depends_on_taint(node: &Node, tainted: &HashSet<&'static str>) -> bool {
+    let mut reads = HashSet::new();
+
+    collect_reads(node, &mut reads);
+
+    reads.iter().any(|name| tainted.contains(name)) || reads_state(node)
+}
+
+/// Returns whether evaluating the node might read the program's state;
+/// functions' bodies aren't inspected, so calls are assumed to.
+fn reads_state(node: &Node) -> bool {
+    matches!(node, Node::LoadState { .. } | Node::Call { .. })
+        || node.children().into_iter().any(reads_state)
+}
+
+pub(crate) fn collect_reads(node: &Node, reads: &mut HashSet<&'static str>) {
+    if let Node::Var(name) = node {
+        reads.insert(name);
+    }
+
+    for child in node.children() {
+        collect_reads(child, reads);
+    }
+}
+
+fn declares(node: &Node, var: &str) -> bool {
+    matches!(node, Node::Let { name, .. } if *name == var)
+        || node
+            .children()
+            .into_iter()
+            .any(|child| declares(child, var))
+}
+
+pub(crate) fn assigns(node: &Node, var: &str) -> usize {
+    let this = match node {
+        Node::Assign { name, .. } if *name == var => 1,
+        _ => 0,
+    };
+
+    this + node
+        .children()
+        .into_iter()
+        .map(|child| assigns(child, var))
+        .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn terminations(src: &'static str) -> Vec<Termination> {
+        parse(src)
+            .unwrap()
+            .termination_report()
+            .loops
+            .into_iter()
+            .map(|lp| lp.termination)
+            .collect()
+    }
+
+    #[test]
+    fn counting_loops() {
+        assert_eq!(
+            vec![Termination::Terminating],
+            terminations("let n = 10; while n > 0 { n = n - 1 }; n"),
+        );
+
+        assert_eq!(
+            vec![Termination::BoundedByInput],
+            terminations("let n = input; while n > 0 { n = n - 1 }; n"),
+        );
+
+        assert_eq!(
+            vec![Termination::Unknown],
+            terminations("let n = 10; while n > 0 { n = n + 1 }; n"),
+        );
+
+        assert_eq!(
+            vec![Termination::Unknown],
+            terminations("let i = 0; let m = 0; while i < { m = m + 2; m } { i = i + 1 }; i"),
+        );
+
+        assert_eq!(
+            vec![Termination::BoundedByInput],
+            terminations("let n = 10; if input > 0 { n = 20 }; while n > 0 { n = n - 1 }; n"),
+        );
+    }
+
+    #[test]
+    fn function_params_are_unknown() {
+        assert_eq!(
+            vec![Termination::BoundedByInput],
+            terminations("fn f(n: int) -> int { while n > 0 { n = n - 1 }; n }; f(10)"),
+        );
+    }
+}
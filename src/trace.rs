@@ -0,0 +1,369 @@
+use crate::{
+    compile_program_with_log, CompileError, CompileOptions, Compiled, FromValue, IntoValue,
+    NodeKind, NodePath, RuntimeContext, Thunk, TypedProgram, Value,
+};
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Step-by-step record of a program's execution, as produced by programs
+/// compiled with [`compile_traced()`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionLog {
+    pub steps: Vec<TraceStep>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    /// Node that's just been evaluated
+    pub node: NodePath,
+
+    pub kind: NodeKind,
+
+    /// Value the node evaluated to
+    pub value: Value,
+
+    /// Variables in scope at the node, in order of their declaration, along
+    /// with their values right after evaluating the node (so `let`s already
+    /// include their own variable)
+    pub vars: Vec<(&'static str, Value)>,
+
+    /// Functions being called at the node, outermost first (empty for nodes
+    /// evaluated outside of functions)
+    pub call_stack: Vec<&'static str>,
+}
+
+impl ExecutionLog {
+    /// Serializes the log into JSON, e.g.:
+    ///
+    /// ```json
+    /// {"steps":[{"node":"body.0.0","kind":"Const","value":0,"vars":{"input":10},"call_stack":[]}, ...]}
+    /// ```
+    ///
+    /// Ints and bools are represented as JSON numbers and booleans, and unit
+    /// as `null`.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"steps\":[");
+
+        for (step_idx, step) in self.steps.iter().enumerate() {
+            if step_idx > 0 {
+                json.push(',');
+            }
+
+            write!(
+                json,
+                "{{\"node\":{},\"kind\":\"{:?}\",\"value\":{},\"vars\":{{",
+                string_to_json(&step.node.to_string()),
+                step.kind,
+                value_to_json(&step.value)
+            )
+            .unwrap();
+
+            for (var_idx, (name, value)) in step.vars.iter().enumerate() {
+                if var_idx > 0 {
+                    json.push(',');
+                }
+
+                write!(json, "{}:{}", string_to_json(name), value_to_json(value)).unwrap();
+            }
+
+            json.push_str("},\"call_stack\":[");
+
+            for (call_idx, name) in step.call_stack.iter().enumerate() {
+                if call_idx > 0 {
+                    json.push(',');
+                }
+
+                json.push_str(&string_to_json(name));
+            }
+
+            json.push_str("]}");
+        }
+
+        json.push_str("]}");
+        json
+    }
+}
+
+fn value_to_json(value: &Value) -> String {
+    match value {
+        Value::Unit => "null".into(),
+        Value::Bool(value) => value.to_string(),
+        Value::Int(value) => value.to_string(),
+
+        // JSON has no representation for infinities and NaNs
+        Value::Float(value) if value.is_finite() => value.to_string(),
+        Value::Float(_) => "null".into(),
+    }
+}
+
+/// Quotes the string, escaping characters that JSON strings can't contain
+/// verbatim.
+fn string_to_json(s: &str) -> String {
+    let mut json = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+/// Compiles the program so that each run returns a log of every evaluated
+/// node alongside the program's output - e.g. to drive visualizations of how
+/// the VM executes a program.
+///
+/// Optimizations are disabled, so that every node in the log refers to a node
+/// of the original program.
+pub fn compile_traced<Input, Output>(
+    prog: TypedProgram<Input, Output>,
+) -> Result<impl Fn(Input) -> (Output, ExecutionLog), CompileError>
+where
+    Input: IntoValue,
+    Output: FromValue,
+{
+    compile_traced_with(prog, &CompileOptions::default())
+}
+
+/// Like [`compile_traced()`], but with given options (e.g. to provide a state
+/// store); [`CompileOptions::disable_optimizations`] is always set.
+pub fn compile_traced_with<Input, Output>(
+    prog: TypedProgram<Input, Output>,
+    opts: &CompileOptions,
+) -> Result<impl Fn(Input) -> (Output, ExecutionLog), CompileError>
+where
+    Input: IntoValue,
+    Output: FromValue,
+{
+    let log = RawLog::default();
+
+    let opts = CompileOptions {
+        disable_optimizations: true,
+        ..opts.clone()
+    };
+
+    let Compiled {
+        thunk,
+        stack_len,
+        report,
+        ..
+    } = compile_program_with_log(
+        prog.into_inner(),
+        Some(Output::ty()),
+        &opts,
+        Some(log.clone()),
+    )?;
+
+    let metrics = opts.metrics;
+
+    if let Some(metrics) = &metrics {
+        metrics.on_compile(&report);
+    }
+
+    Ok(move |input: Input| -> (Output, ExecutionLog) {
+        let started_at = metrics.as_ref().map(|_| Instant::now());
+
+        let mut ctxt = RuntimeContext {
+            stack: vec![Value::Unit; stack_len],
+            depth: 0,
+        };
+
+        ctxt.stack[0] = input.into_value();
+
+        // A previous run might've panicked midway, leaving its steps (and
+        // call stack) behind
+        log.take();
+
+        let output = Output::from_value(thunk(&mut ctxt));
+
+        if let (Some(metrics), Some(started_at)) = (&metrics, started_at) {
+            metrics.on_run(started_at.elapsed());
+        }
+
+        let steps = log
+            .take()
+            .steps
+            .into_iter()
+            .map(|step| TraceStep {
+                node: step.path,
+                kind: step.kind,
+                value: step.value,
+                vars: step.vars,
+                call_stack: step.call_stack,
+            })
+            .collect();
+
+        (output, ExecutionLog { steps })
+    })
+}
+
+pub(crate) type RawLog = Rc<RefCell<Recording>>;
+
+#[derive(Default)]
+pub(crate) struct Recording {
+    steps: Vec<RawStep>,
+
+    /// Functions being called at the moment, outermost first
+    call_stack: Vec<&'static str>,
+}
+
+pub(crate) struct RawStep {
+    path: NodePath,
+    kind: NodeKind,
+    value: Value,
+    vars: Vec<(&'static str, Value)>,
+    call_stack: Vec<&'static str>,
+}
+
+/// Wraps given thunk so that its evaluation gets recorded into `log`, along
+/// with values of `vars` (names and stack slots of variables in scope).
+pub(crate) fn record(
+    log: RawLog,
+    path: NodePath,
+    kind: NodeKind,
+    mut vars: Vec<(&'static str, usize)>,
+    thunk: Thunk,
+) -> Thunk {
+    vars.sort_by_key(|(_, id)| *id);
+
+    Box::new(move |ctxt: &mut RuntimeContext| {
+        let value = thunk(ctxt);
+
+        let vars = vars
+            .iter()
+            .map(|(name, id)| (*name, ctxt.stack[*id].clone()))
+            .collect();
+
+        let mut log = log.borrow_mut();
+        let call_stack = log.call_stack.clone();
+
+        log.steps.push(RawStep {
+            path: path.clone(),
+            kind,
+            value: value.clone(),
+            vars,
+            call_stack,
+        });
+
+        value
+    })
+}
+
+/// Evaluates a call to function `name`, keeping it on `log`'s call stack for
+/// the duration of the call.
+pub(crate) fn record_call(log: &RawLog, name: &'static str, call: impl FnOnce() -> Value) -> Value {
+    log.borrow_mut().call_stack.push(name);
+
+    let value = call();
+
+    log.borrow_mut().call_stack.pop();
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, MemoryStateStore, Node, Program, Type};
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn call_stack() {
+        let prog = parse("fn f(n: int) -> int { n + 1 }; f(input)").unwrap();
+        let f = TypedProgram::<i32, i32>::new(prog)
+            .and_then(compile_traced)
+            .unwrap();
+        let (output, log) = f(1);
+
+        assert_eq!(2, output);
+
+        let call_stacks: Vec<_> = log
+            .steps
+            .iter()
+            .map(|step| (step.kind, step.call_stack.clone()))
+            .collect();
+
+        assert!(call_stacks.contains(&(NodeKind::Add, vec!["f"])));
+        assert!(call_stacks.contains(&(NodeKind::Call, vec![])));
+    }
+
+    #[test]
+    fn panicking_run_doesnt_leak_into_next_one() {
+        let prog = parse("fn f(n: int) -> int { if n > 0 { f(n - 1) } else { 0 } }; f(input)");
+        let f = TypedProgram::<i32, i32>::new(prog.unwrap())
+            .and_then(compile_traced)
+            .unwrap();
+
+        let (_, expected) = f(1);
+
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| f(100_000))).is_err());
+        assert_eq!(expected, f(1).1);
+    }
+
+    #[test]
+    fn json_escapes_names() {
+        let prog = Program {
+            input: Type::Int,
+            output: None,
+            body: Node::Block(vec![
+                Node::Let {
+                    name: "caf\u{e9} \"'\\\n\u{1}",
+                    value: Box::new(Node::Var("input")),
+                },
+                Node::Var("input"),
+            ]),
+        };
+
+        let f = TypedProgram::<i32, i32>::new(prog)
+            .and_then(compile_traced)
+            .unwrap();
+        let json = f(1).1.to_json();
+
+        assert!(
+            json.contains(r#""vars":{"input":1,"café \"'\\\n\u0001":1}"#),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn options() {
+        let prog = parse(r#"let runs = load_state("runs", 0) + 1; save_state("runs", runs); runs"#)
+            .unwrap();
+
+        let opts = CompileOptions {
+            state_store: Some(Rc::new(MemoryStateStore::new())),
+            ..Default::default()
+        };
+
+        let f = TypedProgram::<i32, i32>::new(prog.clone())
+            .and_then(|prog| compile_traced_with(prog, &opts))
+            .unwrap();
+
+        assert_eq!(1, f(0).0);
+
+        let (output, log) = f(0);
+
+        assert_eq!(2, output);
+        assert_eq!(Value::Int(2), log.steps.last().unwrap().value);
+
+        let opts = CompileOptions {
+            max_nodes: Some(5),
+            ..opts
+        };
+
+        assert!(matches!(
+            TypedProgram::<i32, i32>::new(prog).and_then(|prog| compile_traced_with(prog, &opts)),
+            Err(CompileError::TooManyNodes { max: 5, .. }),
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{Node, NodePath, Program};
+use std::collections::HashSet;
+use std::mem;
+
+/// Structural problem found by [`Program::check_well_formed()`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Malformation {
+    /// `let` of a name that's already declared by another `let` of the same
+    /// scope (shadowing variables of outer scopes is fine)
+    DuplicateLet { path: NodePath, name: &'static str },
+
+    /// Function parameter declared more than once
+    DuplicateParam { path: NodePath, name: &'static str },
+
+    /// Read or assignment of a variable before its `let`
+    UndeclaredVar { path: NodePath, name: &'static str },
+
+    /// `fn` of a name that's already declared
+    DuplicateFn { path: NodePath, name: &'static str },
+
+    /// Call of a function before its `fn`
+    UndeclaredFn { path: NodePath, name: &'static str },
+
+    /// Block without any statements, which has no value to evaluate to
+    EmptyBlock { path: NodePath },
+
+    /// Hole that hasn't been filled by [`Program::instantiate()`]
+    UnfilledHole { path: NodePath, id: usize },
+}
+
+impl Program {
+    /// Checks the program for structural problems that don't require type
+    /// checking to discover, reporting all of them at once.
+    pub fn check_well_formed(&self) -> Result<(), Vec<Malformation>> {
+        let mut checker = Checker {
+            path: NodePath::root(),
+            vars: FromIterator::from_iter(vec!["input"]),
+            lets: HashSet::new(),
+            fns: HashSet::new(),
+            problems: Vec::new(),
+        };
+
+        checker.visit(&self.body);
+
+        if checker.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(checker.problems)
+        }
+    }
+}
+
+struct Checker {
+    path: NodePath,
+    vars: HashSet<&'static str>,
+
+    /// Variables declared by `let`s of the innermost scope
+    lets: HashSet<&'static str>,
+
+    fns: HashSet<&'static str>,
+    problems: Vec<Malformation>,
+}
+
+impl Checker {
+    fn visit(&mut self, node: &Node) {
+        // Functions can call themselves and run in frames of their own, where
+        // only their parameters are declared
+        if let Node::Fn { name, params, .. } = node {
+            if !self.fns.insert(name) {
+                self.problems.push(Malformation::DuplicateFn {
+                    path: self.path.clone(),
+                    name,
+                });
+            }
+
+            let mut vars = HashSet::new();
+
+            for (param, _) in params {
+                if !vars.insert(*param) {
+                    self.problems.push(Malformation::DuplicateParam {
+                        path: self.path.clone(),
+                        name: param,
+                    });
+                }
+            }
+
+            self.scoped(vars, |this| this.visit_children(node));
+
+            return;
+        }
+
+        if let Node::Block(_) = node {
+            // Variables declared within the block go out of scope once it ends
+            self.scoped(self.vars.clone(), |this| this.visit_children(node));
+        } else {
+            self.visit_children(node);
+        }
+
+        let path = self.path.clone();
+
+        match node {
+            Node::Let { name, .. } => {
+                if !self.lets.insert(name) {
+                    self.problems
+                        .push(Malformation::DuplicateLet { path, name });
+                }
+
+                self.vars.insert(name);
+            }
+
+            Node::Assign { name, .. } | Node::Var(name) => {
+                if !self.vars.contains(name) {
+                    self.problems
+                        .push(Malformation::UndeclaredVar { path, name });
+                }
+            }
+
+            Node::Call { name, .. } => {
+                if !self.fns.contains(name) {
+                    self.problems
+                        .push(Malformation::UndeclaredFn { path, name });
+                }
+            }
+
+            Node::Block(nodes) if nodes.is_empty() => {
+                self.problems.push(Malformation::EmptyBlock { path });
+            }
+
+            Node::Hole(id, _) => {
+                self.problems
+                    .push(Malformation::UnfilledHole { path, id: *id });
+            }
+
+            _ => {}
+        }
+    }
+
+    fn visit_children(&mut self, node: &Node) {
+        for (idx, child) in node.children().into_iter().enumerate() {
+            self.path.push(idx);
+
+            // Branches get scopes of their own, like blocks do
+            if node.is_branch(idx) {
+                self.scoped(self.vars.clone(), |this| this.visit(child));
+            } else {
+                self.visit(child);
+            }
+
+            self.path.pop();
+        }
+    }
+
+    /// Visits whatever `visit` visits in a new scope, starting with `vars`
+    /// declared, and brings back the current scope afterwards.
+    fn scoped(&mut self, vars: HashSet<&'static str>, visit: impl FnOnce(&mut Self)) {
+        let outer_vars = mem::replace(&mut self.vars, vars);
+        let outer_lets = mem::take(&mut self.lets);
+
+        visit(self);
+
+        self.vars = outer_vars;
+        self.lets = outer_lets;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Malformation, Node, Program, Type, Value};
+
+    fn problems(src: &'static str) -> Vec<Malformation> {
+        parse(src)
+            .unwrap()
+            .check_well_formed()
+            .err()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn duplicate_let() {
+        assert_eq!(
+            vec![Malformation::DuplicateLet {
+                path: vec![1].into(),
+                name: "x",
+            }],
+            problems("let x = 1; let x = 2; x"),
+        );
+    }
+
+    #[test]
+    fn shadowing() {
+        assert!(
+            problems("let x = 1; { let x = 2; x }; if x > 0 { let x = 3; x } else { x }")
+                .is_empty()
+        );
+        assert!(problems("fn f(x: int) -> int { let x = x + 1; x }; f(input)").is_empty());
+    }
+
+    #[test]
+    fn branch_lets_dont_leak() {
+        // while false let x = 1; x
+        let prog = Program {
+            input: Type::Int,
+            output: None,
+            body: Node::Block(vec![
+                Node::While {
+                    cond: Box::new(Node::Const(Value::Bool(false))),
+                    body: Box::new(Node::Let {
+                        name: "x",
+                        value: Box::new(Node::Const(Value::Int(1))),
+                    }),
+                },
+                Node::Var("x"),
+            ]),
+        };
+
+        assert_eq!(
+            Err(vec![Malformation::UndeclaredVar {
+                path: vec![1].into(),
+                name: "x",
+            }]),
+            prog.check_well_formed(),
+        );
+    }
+
+    #[test]
+    fn undeclared_vars() {
+        assert_eq!(
+            vec![
+                Malformation::UndeclaredVar {
+                    path: vec![0].into(),
+                    name: "x",
+                },
+                Malformation::UndeclaredVar {
+                    path: vec![2, 1].into(),
+                    name: "y",
+                },
+            ],
+            problems("x = 1; let x = 2; x + y"),
+        );
+    }
+
+    #[test]
+    fn fns() {
+        assert_eq!(
+            vec![
+                Malformation::UndeclaredFn {
+                    path: vec![0].into(),
+                    name: "f",
+                },
+                Malformation::DuplicateParam {
+                    path: vec![1].into(),
+                    name: "n",
+                },
+                Malformation::DuplicateFn {
+                    path: vec![2].into(),
+                    name: "f",
+                },
+            ],
+            problems("f(1); fn f(n: int, n: int) -> int { n }; fn f() -> int { f() }; f(1, 2)"),
+        );
+    }
+
+    #[test]
+    fn empty_blocks_and_holes() {
+        let prog = Program {
+            input: Type::Int,
+            output: None,
+            body: Node::Block(vec![Node::Block(vec![]), Node::Hole(7, Type::Int)]),
+        };
+
+        assert_eq!(
+            Err(vec![
+                Malformation::EmptyBlock {
+                    path: vec![0].into(),
+                },
+                Malformation::UnfilledHole {
+                    path: vec![1].into(),
+                    id: 7,
+                },
+            ]),
+            prog.check_well_formed(),
+        );
+    }
+}